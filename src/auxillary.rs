@@ -1,6 +1,8 @@
 use CellContainer;
+use std::f64::consts::PI;
 use rand::Rng;
-use cell::{Cell, ConnectionState};
+use cell::{Cell, Connection, ConnectionInheritance, ConnectionKind, ConnectionState, Genome, DEFAULT_DIVISION,
+           ROD_HOOKE};
 use CellGraph;
 use zoom::{self, BasicParticle};
 use nalgebra::Vector2;
@@ -8,6 +10,7 @@ use num::Zero;
 use petgraph::stable_graph::{NodeIndex, EdgeIndex};
 use petgraph::Direction;
 use itertools::Itertools;
+use boolinator::Boolinator;
 
 use SIZE_SCALE;
 
@@ -20,8 +23,6 @@ const NEWTON_STATIC: f64 = 50000.0;
 
 const INERTIA: f64 = 1.0;
 
-const CELL_SPAWN_PROBABILITY: f64 = 0.1 * SIZE_SCALE * SIZE_SCALE;
-
 pub fn area_box() -> zoom::Box<Vector2<f64>> {
     zoom::Box {
         origin: Vector2::new(0.0, 0.0),
@@ -37,7 +38,7 @@ pub fn compute_newton_coefficient(edge: (f64, f64)) -> f64 {
     NEWTON_STATIC + NEWTON_DYNAMIC * (edge.0 + edge.1) * 0.5
 }
 
-fn map_node_to_mag(cc: &CellContainer) -> f64 {
+pub fn map_node_to_mag(cc: &CellContainer) -> f64 {
     cc.delta.as_ref().map(|d| d.repulsion).unwrap_or(0.5)
 }
 
@@ -45,18 +46,16 @@ pub fn cell_physics_interactions(graph: &mut CellGraph) {
     for nix in graph.node_indices() {
         let mut walker = graph.neighbors_undirected(nix).detach();
         while let Some((eix, tnix)) = walker.next(&graph) {
-
-            let hooke_coefficient = compute_hooke_coefficient(graph
-                                                                  .edge_weight(eix)
-                                                                  .map(|&(ref d0, ref d1)| {
-                                                                           (d0.elasticity,
-                                                                            d1.elasticity)
-                                                                       })
-                                                                  .unwrap());
+            let connection = graph.edge_weight(eix).unwrap();
+            let hooke_coefficient = compute_hooke_coefficient((connection.source.elasticity,
+                                                               connection.target.elasticity));
 
             let tcc = graph.node_weight(nix).unwrap();
             tcc.cell
-                .interact_connection(&graph.node_weight(tnix).unwrap().cell, hooke_coefficient);
+                .interact_connection(&graph.node_weight(tnix).unwrap().cell,
+                                     connection.kind,
+                                     hooke_coefficient,
+                                     connection.rest_length());
         }
     }
 
@@ -71,6 +70,26 @@ pub fn cell_physics_interactions(graph: &mut CellGraph) {
     }
 }
 
+/// Records the distance from every cell to its closest neighbor for feeding.
+pub fn record_closest_distances(graph: &CellGraph) {
+    for nv in graph.node_indices().combinations(2) {
+        graph[nv[0]].cell.observe_neighbor(&graph[nv[1]].cell);
+    }
+}
+
+/// Finds the cell closest to a point, taking wrapping into account.
+pub fn closest_cell(graph: &CellGraph, point: Vector2<f64>) -> Option<NodeIndex<u32>> {
+    use nalgebra::Norm;
+    use zoom::Toroid;
+    graph.node_indices()
+        .map(|nix| (nix, area_box().wrap_delta(graph[nix].cell.position() - point).norm_squared()))
+        .fold(None, |best: Option<(NodeIndex<u32>, f64)>, (nix, d)| match best {
+            Some((_, bd)) if bd <= d => best,
+            _ => Some((nix, d)),
+        })
+        .map(|(nix, _)| nix)
+}
+
 pub fn random_point<R: Rng>(rng: &mut R) -> Vector2<f64> {
     let mut central_rand = || 2.0 * rng.next_f64() - 1.0;
     area_box().origin +
@@ -78,47 +97,126 @@ pub fn random_point<R: Rng>(rng: &mut R) -> Vector2<f64> {
                  area_box().offset.y * central_rand())
 }
 
-pub fn generate_cells<R: Rng>(graph: &mut CellGraph, rng: &mut R) {
-    if rng.next_f64() < CELL_SPAWN_PROBABILITY {
-        let particle = BasicParticle::new(1.0, random_point(rng), Vector2::zero(), INERTIA);
-        graph.add_node(CellContainer {
-                           cell: Cell::new_rand(rng, particle),
-                           delta: None,
-                       });
+/// Adds a new cell at a position with the given genome, or a random one if there is none.
+pub fn spawn_cell<R: Rng>(graph: &mut CellGraph,
+                          position: Vector2<f64>,
+                          genome: Option<Genome>,
+                          rng: &mut R)
+                          -> NodeIndex<u32> {
+    let particle = BasicParticle::new(1.0, position, Vector2::zero(), INERTIA);
+    let cell = match genome {
+        Some(genome) => Cell::new(genome, particle),
+        None => Cell::new_rand(rng, particle),
+    };
+    graph.add_node(CellContainer::new(cell))
+}
+
+/// The stiffness a connection pulls with, or `None` if it exerts no force.
+fn connection_stiffness(connection: &Connection) -> Option<f64> {
+    match connection.kind {
+        ConnectionKind::Spring | ConnectionKind::Rope => {
+            Some(compute_hooke_coefficient((connection.source.elasticity, connection.target.elasticity)))
+        }
+        ConnectionKind::Rod => Some(ROD_HOOKE),
+        ConnectionKind::Signal => None,
     }
 }
 
-pub fn divide_cell<R: Rng>(graph: &mut CellGraph, nix: NodeIndex<u32>, rng: &mut R) {
+/// Finds the angle of the direction towards the neighbor with the stiffest connection, if there are any which
+/// exert a force.
+fn strongest_connection_angle(graph: &CellGraph, nix: NodeIndex<u32>) -> Option<f64> {
+    use nalgebra::Norm;
+    use zoom::Toroid;
+    let mut walker = graph.neighbors_undirected(nix).detach();
+    let mut strongest: Option<(f64, NodeIndex<u32>)> = None;
+    while let Some((eix, tnix)) = walker.next(graph) {
+        let hooke = match connection_stiffness(&graph[eix]) {
+            Some(hooke) => hooke,
+            None => continue,
+        };
+        if strongest.map(|(h, _)| hooke > h).unwrap_or(true) {
+            strongest = Some((hooke, tnix));
+        }
+    }
+    strongest.map(|(_, tnix)| area_box().wrap_delta(graph[tnix].cell.position() - graph[nix].cell.position()))
+        .and_then(|delta| (delta.norm_squared() > 0.0).as_some(delta.y.atan2(delta.x)))
+}
+
+pub fn divide_cell<R: Rng>(graph: &mut CellGraph, nix: NodeIndex<u32>, rng: &mut R) -> NodeIndex<u32> {
     use petgraph::Direction::*;
     use petgraph::visit::EdgeRef;
-    let new_energy = graph[nix].cell.energy() / 2;
-    graph[nix].cell.set_energy(new_energy);
+    let (inherit_type, energy_split, division_angle) = graph[nix]
+        .delta
+        .as_ref()
+        .map(|d| (d.inherit_type, d.energy_split, d.division_angle))
+        .unwrap_or(DEFAULT_DIVISION);
+    // The parent decides who keeps each connection before the energy is split so both pay for the decision.
+    let position = graph[nix].cell.position();
+    let connections = graph
+        .edges_directed(nix, Outgoing)
+        .map(|e| (Outgoing, e.id(), e.target()))
+        .chain(graph.edges_directed(nix, Incoming).map(|e| (Incoming, e.id(), e.source())))
+        .collect::<Vec<_>>();
+    let connection_states = connections
+        .iter()
+        .map(|&(direction, eix, other)| compute_connection_state(graph, position, direction, eix, other))
+        .collect::<Vec<_>>();
+    let inheritances = graph[nix].cell.run_connection_inheritance(connection_states.clone());
+    let energy = graph[nix].cell.energy();
+    let child_energy = (energy as f64 * energy_split) as usize;
+    graph[nix].cell.set_energy(energy - child_energy);
     let mut new_cell = graph[nix].cell.clone();
+    new_cell.set_energy(child_energy);
+    if !inherit_type {
+        new_cell.set_cell_type(0);
+    }
     new_cell.mutate(rng);
-    new_cell.random_shift(rng);
-    let cc = CellContainer {
-        cell: new_cell,
-        delta: None,
-    };
-
+    new_cell.make_newborn();
+    // Without any connections the angle is relative to the x axis.
+    let division_angle = division_angle.unwrap_or_else(|| rng.gen_range(0.0, 2.0 * PI));
+    new_cell.shift(strongest_connection_angle(graph, nix).unwrap_or(0.0) + division_angle);
+    let mut cc = CellContainer::new(new_cell);
+    cc.species = graph[nix].species;
     let nnix = graph.add_node(cc);
-    graph.update_edge(nix, nnix, Default::default());
-    for edge_target in graph
-            .edges_directed(nix, Outgoing)
-            .map(|e| e.target())
-            .collect::<Vec<_>>() {
-        if graph.find_edge(edge_target, nnix).is_none() {
-            graph.update_edge(nnix, edge_target, Default::default());
+    for ((&(direction, eix, other), inheritance), connection_state) in
+        connections.iter().zip(inheritances).zip(connection_states) {
+        let (source, target) = match direction {
+            Outgoing => (nnix, other),
+            Incoming => (other, nnix),
+        };
+        match inheritance {
+            ConnectionInheritance::Parent => {}
+            ConnectionInheritance::Child => {
+                let weight = graph.remove_edge(eix).unwrap();
+                graph.update_edge(source, target, weight);
+            }
+            ConnectionInheritance::Both => {
+                // The parent chooses the kind of every connection that is newly formed.
+                let kind = graph[nix].cell.run_connection_kind(connection_state);
+                graph.update_edge(source, target, Connection { kind: kind, ..Default::default() });
+            }
         }
     }
-    for edge_source in graph
-            .edges_directed(nix, Outgoing)
-            .map(|e| e.source())
-            .collect::<Vec<_>>() {
-        if graph.find_edge(nnix, edge_source).is_none() {
-            graph.update_edge(edge_source, nnix, Default::default());
-        }
+    let eix = graph.update_edge(nix, nnix, Default::default());
+    let child_state = compute_connection_state(graph, position, Outgoing, eix, nnix);
+    graph[eix].kind = graph[nix].cell.run_connection_kind(child_state);
+    nnix
+}
+
+/// Finds the groups of cells which are connected together, ignoring edge direction.
+pub fn connected_components(graph: &CellGraph) -> Vec<Vec<NodeIndex<u32>>> {
+    use petgraph::unionfind::UnionFind;
+    use petgraph::visit::{EdgeRef, IntoEdgeReferences, NodeIndexable};
+    use std::collections::BTreeMap;
+    let mut sets = UnionFind::new(graph.node_bound());
+    for er in graph.edge_references() {
+        sets.union(er.source().index(), er.target().index());
     }
+    let mut components: BTreeMap<usize, Vec<NodeIndex<u32>>> = BTreeMap::new();
+    for nix in graph.node_indices() {
+        components.entry(sets.find(nix.index())).or_insert_with(Vec::new).push(nix);
+    }
+    components.into_iter().map(|(_, nodes)| nodes).collect()
 }
 
 fn compute_connection_state(graph: &mut CellGraph,
@@ -130,13 +228,15 @@ fn compute_connection_state(graph: &mut CellGraph,
     use nalgebra::Norm;
     use zoom::Toroid;
     let sent = match (direction, graph.edge_weight(target_edge).unwrap()) {
-        (Direction::Outgoing, e) => e.1.signal.clone(),
-        (Direction::Incoming, e) => e.0.signal.clone(),
+        (Direction::Outgoing, c) => c.target.signals.clone(),
+        (Direction::Incoming, c) => c.source.signals.clone(),
     };
-    let length = area_box().wrap_delta(graph.node_weight(target_node).unwrap().cell.position() - source_position).norm();
+    let target = &graph.node_weight(target_node).unwrap().cell;
+    let length = area_box().wrap_delta(target.position() - source_position).norm();
     ConnectionState {
         incoming: sent,
         length: length,
+        cell_type: target.cell_type(),
     }
 }
 
@@ -164,10 +264,15 @@ pub fn update_deltas(graph: &mut CellGraph, nix: NodeIndex<u32>, direction: Dire
     let mut walker = graph.neighbors_directed(nix, direction).detach();
     let mut counter = 0..;
     while let (Some(ix), Some(eix)) = (counter.next(), walker.next_edge(&graph)) {
-        graph.edge_weight_mut(eix).unwrap().0 = deltas
+        let delta = deltas
             .as_ref()
             .map(|deltas| deltas[ix].clone())
             .unwrap_or_default();
+        let connection = graph.edge_weight_mut(eix).unwrap();
+        match direction {
+            Direction::Outgoing => connection.source = delta,
+            Direction::Incoming => connection.target = delta,
+        }
     }
 }
 
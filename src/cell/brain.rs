@@ -1,14 +1,53 @@
 use gapush;
 
 use gapush::simple::{Chromosome, SimpleInstruction, PlainOp};
-use rand::Rng;
+use rand::{Isaac64Rng, Rng};
+use super::{CHEMICAL_COUNT, SIGNAL_CHANNELS};
 use rand::distributions::{Exp, IndependentSample};
 
 const DEFAULT_LAMBDA: f64 = 8192.0;
 const LAMBDA_SELF_POINT: f64 = 512.0;
 const MAXIMUM_MUTATES: usize = 1024;
 
-const INIT_EXECUTION_TIME: usize = 512;
+/// The execution cost of every chromosome unless it is given its own.
+const DEFAULT_EXECUTION_COST: ExecutionCost = ExecutionCost {
+    budget: 512,
+    min_budget: 64,
+    max_budget: 4096,
+    price: 20.0,
+};
+/// The execution cost of each chromosome in the order of `CHROMOSOME_NAMES`.
+const EXECUTION_COSTS: [ExecutionCost; CHROMOSOME_COUNT] = [DEFAULT_EXECUTION_COST, // init
+                                                            DEFAULT_EXECUTION_COST, // cycle
+                                                            DEFAULT_EXECUTION_COST, // connection_elasticity
+                                                            DEFAULT_EXECUTION_COST, // connection_signal
+                                                            DEFAULT_EXECUTION_COST, // connection_sever
+                                                            DEFAULT_EXECUTION_COST, // repulsion
+                                                            DEFAULT_EXECUTION_COST, // die
+                                                            DEFAULT_EXECUTION_COST, // divide
+                                                            DEFAULT_EXECUTION_COST, // emit
+                                                            DEFAULT_EXECUTION_COST, // connection_inherit
+                                                            DEFAULT_EXECUTION_COST]; // connection_kind
+/// Whether each genome carries its own budgets, which evolve within the bounds of `EXECUTION_COSTS`.
+const EVOLVABLE_BUDGETS: bool = false;
+/// The chance of each budget changing when a genome mutates.
+const BUDGET_MUTATION_PROBABILITY: f64 = 0.05;
+/// The most a budget is scaled up or down by in a single mutation.
+const BUDGET_MUTATION_FACTOR: f64 = 2.0;
+
+// The position of each chromosome in `CHROMOSOME_NAMES`.
+const INIT: usize = 0;
+const CYCLE: usize = 1;
+const CONNECTION_ELASTICITY: usize = 2;
+const CONNECTION_SIGNAL: usize = 3;
+const CONNECTION_SEVER: usize = 4;
+const REPULSION: usize = 5;
+const DIE: usize = 6;
+const DIVIDE: usize = 7;
+const EMIT: usize = 8;
+const CONNECTION_INHERIT: usize = 9;
+const CONNECTION_KIND: usize = 10;
+
 const INIT_LEN: usize = 128;
 const INIT_CROSSOVERS: usize = 4;
 const CYCLE_LEN: usize = 128;
@@ -25,17 +64,53 @@ const DIE_LEN: usize = 128;
 const DIE_CROSSOVERS: usize = 4;
 const DIVIDE_LEN: usize = 128;
 const DIVIDE_CROSSOVERS: usize = 4;
+const EMIT_LEN: usize = 128;
+const EMIT_CROSSOVERS: usize = 4;
+const CONNECTION_INHERIT_LEN: usize = 128;
+const CONNECTION_INHERIT_CROSSOVERS: usize = 4;
+const CONNECTION_KIND_LEN: usize = 128;
+const CONNECTION_KIND_CROSSOVERS: usize = 4;
+
+/// The number of chromosomes in a genome.
+pub const CHROMOSOME_COUNT: usize = 11;
+/// The names of the chromosomes in the order they appear in the genome.
+pub const CHROMOSOME_NAMES: [&'static str; CHROMOSOME_COUNT] = ["init",
+                                                                "cycle",
+                                                                "connection_elasticity",
+                                                                "connection_signal",
+                                                                "connection_sever",
+                                                                "repulsion",
+                                                                "die",
+                                                                "divide",
+                                                                "emit",
+                                                                "connection_inherit",
+                                                                "connection_kind"];
+
+/// Limits how long a chromosome runs and sets how much energy running it costs.
+#[derive(Copy, Clone, Debug)]
+struct ExecutionCost {
+    /// The most cycles the chromosome executes each time it runs.
+    budget: usize,
+    /// The bounds the budget evolves within when `EVOLVABLE_BUDGETS` is set.
+    min_budget: usize,
+    max_budget: usize,
+    /// The energy each executed cycle costs.
+    price: f64,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Genome {
     /// Runs to initialize the cell; this ignores any yielded instructions.
     init: Chromosome,
-    /// Runs each cycle before anything else; is passed the cell's energy on the float stack.
+    /// Runs each cycle before anything else; is passed the cell's type, the concentration of each chemical at the
+    /// cell, the cell's age and then the cell's energy on the float stack. An i64 yielded from it becomes the cell's new type.
     cycle: Chromosome,
-    /// Determines connection elasticity; this is passed the connection instruction on the instruction stack
-    /// and the length on the float stack.
+    /// Determines connection elasticity; this is passed the instruction of every signal channel on the
+    /// instruction stack and the length on the float stack. It is then run again to get an i64 which indicates
+    /// the rest length of the connection.
     connection_elasticity: Chromosome,
-    /// Determines connection signal; this is to be executed directly after connection_elasticity.
+    /// Determines connection signal; this is to be executed directly after connection_elasticity, once for each
+    /// signal channel.
     connection_signal: Chromosome,
     /// Determines if the connection will be severed; this is to be executed directly after connection_signal.
     connection_sever: Chromosome,
@@ -44,12 +119,56 @@ pub struct Genome {
     /// Doesn't pass anything, but tries to get a bool which determines if the cell should die.
     die: Chromosome,
     /// Doesn't pass anything, but tries to get a bool which determines if the cell should divide.
+    /// If it should, it is then run again three times: first to get a bool which determines if the child keeps
+    /// the cell's type, then to get an i64 which indicates the share of energy the child gets, and finally
+    /// to get an i64 which indicates the angle the child is placed at relative to the strongest connection.
     divide: Chromosome,
+    /// Runs once for each chemical, trying to get an i64 back which indicates how much of it to emit.
+    #[serde(default = "default_emit")]
+    emit: Chromosome,
+    /// Runs at division once for each connection; is passed the other cell's type and the length on the float
+    /// stack and tries to get an i64 back which determines whether the parent, the child or both keep it.
+    #[serde(default = "default_connection_inherit")]
+    connection_inherit: Chromosome,
+    /// Runs whenever a connection is formed; is passed the other cell's type and the length on the float stack
+    /// and tries to get an i64 back which determines the kind of connection.
+    #[serde(default = "default_connection_kind")]
+    connection_kind: Chromosome,
 
     lambda: f64,
+    /// The budget of each chromosome in the order of `CHROMOSOME_NAMES`; only used when `EVOLVABLE_BUDGETS` is set.
+    #[serde(default = "default_budgets")]
+    budgets: [usize; CHROMOSOME_COUNT],
 }
 
 impl Genome {
+    /// Produces a human-readable listing of every chromosome's instructions.
+    ///
+    /// When `eliminate_dead_code` is set, instructions which can never affect a yielded value are left out; see
+    /// `live_genes` for what that can prove.
+    pub fn disassemble(&self, eliminate_dead_code: bool) -> String {
+        use std::fmt::Write;
+        let mut listing = String::new();
+        writeln!(listing, "lambda: {}", self.lambda).unwrap();
+        for (cix, (name, chromosome)) in CHROMOSOME_NAMES.iter().zip(self.chromosomes().iter()).enumerate() {
+            writeln!(listing,
+                     "\n{} ({} genes, budget {}):",
+                     name,
+                     chromosome.gene_len(),
+                     self.budget(cix))
+                .unwrap();
+            let genes = if eliminate_dead_code {
+                live_genes(&chromosome.genes)
+            } else {
+                chromosome.genes.iter().enumerate().collect()
+            };
+            for (ix, ins) in genes {
+                writeln!(listing, "{:>6}: {:?}", ix, ins).unwrap();
+            }
+        }
+        listing
+    }
+
     fn new_rand<R: Rng>(rng: &mut R) -> Genome {
         Genome {
             init: Chromosome::new_rand(rng, INIT_LEN, INIT_CROSSOVERS),
@@ -66,7 +185,13 @@ impl Genome {
             repulsion: Chromosome::new_rand(rng, REPULSION_LEN, REPULSION_CROSSOVERS),
             die: Chromosome::new_rand(rng, DIE_LEN, DIE_CROSSOVERS),
             divide: Chromosome::new_rand(rng, DIVIDE_LEN, DIVIDE_CROSSOVERS),
+            emit: Chromosome::new_rand(rng, EMIT_LEN, EMIT_CROSSOVERS),
+            connection_inherit: Chromosome::new_rand(rng,
+                                                     CONNECTION_INHERIT_LEN,
+                                                     CONNECTION_INHERIT_CROSSOVERS),
+            connection_kind: Chromosome::new_rand(rng, CONNECTION_KIND_LEN, CONNECTION_KIND_CROSSOVERS),
             lambda: DEFAULT_LAMBDA,
+            budgets: default_budgets(),
         }
     }
 
@@ -91,6 +216,18 @@ impl Genome {
         self.repulsion.mutate(MAXIMUM_MUTATES, &exp, rng);
         self.die.mutate(MAXIMUM_MUTATES, &exp, rng);
         self.divide.mutate(MAXIMUM_MUTATES, &exp, rng);
+        self.emit.mutate(MAXIMUM_MUTATES, &exp, rng);
+        self.connection_inherit.mutate(MAXIMUM_MUTATES, &exp, rng);
+        self.connection_kind.mutate(MAXIMUM_MUTATES, &exp, rng);
+
+        if EVOLVABLE_BUDGETS {
+            for (budget, cost) in self.budgets.iter_mut().zip(EXECUTION_COSTS.iter()) {
+                if rng.next_f64() < BUDGET_MUTATION_PROBABILITY {
+                    let factor = BUDGET_MUTATION_FACTOR.powf(rng.gen_range(-1.0, 1.0));
+                    *budget = ((*budget as f64 * factor) as usize).max(cost.min_budget).min(cost.max_budget);
+                }
+            }
+        }
     }
 
     fn mate(&self, other: &Self) -> Self {
@@ -104,7 +241,78 @@ impl Genome {
             repulsion: self.repulsion.mate(&other.repulsion),
             die: self.die.mate(&other.die),
             divide: self.divide.mate(&other.divide),
+            emit: self.emit.mate(&other.emit),
+            connection_inherit: self.connection_inherit.mate(&other.connection_inherit),
+            connection_kind: self.connection_kind.mate(&other.connection_kind),
             lambda: (self.lambda + other.lambda) * 0.5,
+            budgets: {
+                let mut budgets = [0; CHROMOSOME_COUNT];
+                for ((budget, a), b) in budgets.iter_mut().zip(self.budgets.iter()).zip(other.budgets.iter()) {
+                    *budget = (a + b) / 2;
+                }
+                budgets
+            },
+        }
+    }
+
+    /// Gets the most cycles a chromosome may execute each time it runs.
+    fn budget(&self, chromosome: usize) -> usize {
+        if EVOLVABLE_BUDGETS {
+            self.budgets[chromosome]
+        } else {
+            EXECUTION_COSTS[chromosome].budget
+        }
+    }
+
+    /// Gets every chromosome in the order of `CHROMOSOME_NAMES`.
+    fn chromosomes(&self) -> [&Chromosome; CHROMOSOME_COUNT] {
+        [&self.init,
+         &self.cycle,
+         &self.connection_elasticity,
+         &self.connection_signal,
+         &self.connection_sever,
+         &self.repulsion,
+         &self.die,
+         &self.divide,
+         &self.emit,
+         &self.connection_inherit,
+         &self.connection_kind]
+    }
+
+    /// Computes the edit distance between each pair of corresponding chromosomes in the order of
+    /// `CHROMOSOME_NAMES`.
+    pub fn chromosome_distances(&self, other: &Genome) -> [usize; CHROMOSOME_COUNT] {
+        let mut distances = [0; CHROMOSOME_COUNT];
+        for ((distance, a), b) in distances.iter_mut()
+                .zip(self.chromosomes().iter())
+                .zip(other.chromosomes().iter()) {
+            *distance = edit_distance(&a.genes, &b.genes);
+        }
+        distances
+    }
+
+    /// Gets the instructions of each chromosome in the order of `CHROMOSOME_NAMES`.
+    pub fn chromosome_genes(&self) -> [&[SimpleInstruction]; CHROMOSOME_COUNT] {
+        let chromosomes = self.chromosomes();
+        let mut genes: [&[SimpleInstruction]; CHROMOSOME_COUNT] = [&[]; CHROMOSOME_COUNT];
+        for (genes, chromosome) in genes.iter_mut().zip(chromosomes.iter()) {
+            *genes = &chromosome.genes;
+        }
+        genes
+    }
+
+    /// Computes how similar two genomes are from 0 (nothing in common) to 1 (identical chromosomes).
+    pub fn relatedness(&self, other: &Genome) -> f64 {
+        let distance = self.chromosome_distances(other).iter().sum::<usize>();
+        let max_distance = self.chromosomes()
+            .iter()
+            .zip(other.chromosomes().iter())
+            .map(|(a, b)| a.gene_len().max(b.gene_len()))
+            .sum::<usize>();
+        if max_distance == 0 {
+            1.0
+        } else {
+            1.0 - distance as f64 / max_distance as f64
         }
     }
 
@@ -118,6 +326,9 @@ impl Genome {
             .and_then(|n| n.checked_sub(self.repulsion.gene_len()))
             .and_then(|n| n.checked_sub(self.die.gene_len()))
             .and_then(|n| n.checked_sub(self.divide.gene_len()))
+            .and_then(|n| n.checked_sub(self.emit.gene_len()))
+            .and_then(|n| n.checked_sub(self.connection_inherit.gene_len()))
+            .and_then(|n| n.checked_sub(self.connection_kind.gene_len()))
             .unwrap_or(0)
     }
 }
@@ -127,119 +338,178 @@ pub struct Brain {
     genome: Genome,
     machine:
         gapush::Machine<SimpleInstruction, fn() -> SimpleInstruction, fn() -> i64, fn() -> f64>,
+    /// The cycles each chromosome executed since they were last taken.
+    cycles: [usize; CHROMOSOME_COUNT],
+    /// The energy the executed cycles cost since it was last taken.
+    cost: f64,
 }
 
 impl Brain {
     pub fn new_rand<R: Rng>(max_size: usize, rng: &mut R) -> Brain {
-        let genome = Genome::new_rand(rng);
-        let mut machine = gapush::Machine::new(max_size,
+        Brain::new(Genome::new_rand(rng), max_size)
+    }
+
+    pub fn new(genome: Genome, max_size: usize) -> Brain {
+        let machine = gapush::Machine::new(max_size,
                                                instruction_handler as fn() -> SimpleInstruction,
                                                int_handler as fn() -> i64,
                                                float_handler as fn() -> f64);
-        // Execute the initialization routine.
-        machine.provide_and_cycle_until(INIT_EXECUTION_TIME, (&genome.init).into());
-        Brain {
+        let mut brain = Brain {
             genome: genome,
             machine: machine,
-        }
+            cycles: [0; CHROMOSOME_COUNT],
+            cost: 0.0,
+        };
+        // Execute the initialization routine.
+        brain.execute(INIT);
+        brain
     }
 
     pub fn mate(&self, other: &Self, child_max_size: usize) -> Brain {
-        let genome = self.genome.mate(&other.genome);
-        let mut machine = gapush::Machine::new(child_max_size,
-                                               instruction_handler as fn() -> SimpleInstruction,
-                                               int_handler as fn() -> i64,
-                                               float_handler as fn() -> f64);
-        // Execute the initialization routine.
-        machine.provide_and_cycle_until(INIT_EXECUTION_TIME, (&genome.init).into());
-        Brain {
-            genome: genome,
-            machine: machine,
-        }
+        Brain::new(self.genome.mate(&other.genome), child_max_size)
     }
 
     pub fn mutate<R: Rng>(&mut self, rng: &mut R) {
         self.genome.mutate(rng);
     }
 
+    pub fn genome(&self) -> &Genome {
+        &self.genome
+    }
+
     pub fn set_size(&mut self, size: usize) {
         self.machine.state.max_size = self.genome.leftover_size_from(size);
     }
 
-    /// Runs the cycle and returns the number of cycles executed.
-    pub fn run_cycle(&mut self, energy: f64) -> usize {
+    /// Runs the cycle. Gets an i64 back which is the desired cell type.
+    pub fn run_cycle(&mut self, cell_type: i64, age: f64, energy: f64, chemicals: &[f64]) -> Option<i64> {
+        self.machine.state.push_float(cell_type as f64).ok();
+        for &concentration in chemicals {
+            self.machine.state.push_float(concentration).ok();
+        }
+        self.machine.state.push_float(age).ok();
         self.machine.state.push_float(energy).ok();
-        self.machine
-            .provide_and_cycle_until(INIT_EXECUTION_TIME, (&self.genome.cycle).into())
-            .1
+        self.execute(CYCLE).and_then(as_i64)
     }
 
-    /// Runs the connection chromosomes. Gives back an i64 that corresponds to the desired elasticity and
-    /// an instruction corresponding to the signal.
+    /// Runs the connection chromosomes. Gives back an i64 that corresponds to the desired elasticity, another
+    /// for the desired rest length, an instruction for each signal channel and a bool that indicates whether to
+    /// sever the connection.
+    ///
+    /// The other cell's type is pushed on the float stack along with the length.
+    /// The incoming signals are pushed in channel order, so the last channel ends up on top of the stack.
+    /// The signal chromosome is run once per channel to produce the outgoing signals.
     pub fn run_connection(&mut self,
                           length: f64,
-                          ins: SimpleInstruction)
-                          -> (Option<i64>, Option<SimpleInstruction>, Option<bool>, usize) {
+                          cell_type: i64,
+                          incoming: Vec<SimpleInstruction>)
+                          -> (Option<i64>, Option<i64>, Vec<Option<SimpleInstruction>>, Option<bool>) {
+        self.machine.state.push_float(cell_type as f64).ok();
         self.machine.state.push_float(length).ok();
-        self.machine.state.push_ins(ins).ok();
-        let (elasticity, elen) =
-            self.machine
-                .provide_and_cycle_until(INIT_EXECUTION_TIME,
-                                         (&self.genome.connection_elasticity).into());
-        let (signal, slen) =
-            self.machine
-                .provide_and_cycle_until(INIT_EXECUTION_TIME,
-                                         (&self.genome.connection_signal).into());
-        let (sever, svlen) =
-            self.machine
-                .provide_and_cycle_until(INIT_EXECUTION_TIME,
-                                         (&self.genome.connection_sever).into());
-        (elasticity.and_then(|ins| match ins {
-                                 SimpleInstruction::Pushi64(n) => Some(n),
-                                 _ => None,
-                             }),
-         signal,
-         sever.and_then(|ins| match ins {
-                            SimpleInstruction::Pushb(b) => Some(b),
-                            _ => None,
-                        }),
-         elen + slen + svlen)
+        for ins in incoming {
+            self.machine.state.push_ins(ins).ok();
+        }
+        let elasticity = self.execute(CONNECTION_ELASTICITY).and_then(as_i64);
+        let rest_length = self.execute(CONNECTION_ELASTICITY).and_then(as_i64);
+        let signals = (0..SIGNAL_CHANNELS).map(|_| self.execute(CONNECTION_SIGNAL)).collect::<Vec<_>>();
+        let sever = self.execute(CONNECTION_SEVER).and_then(as_bool);
+        (elasticity, rest_length, signals, sever)
     }
 
     /// Runs the repulsion chromosome. Gets an i64 back that indicates the desired repulsion.
-    pub fn run_repulsion(&mut self) -> (Option<i64>, usize) {
-        let (repulsion, len) =
-            self.machine
-                .provide_and_cycle_until(INIT_EXECUTION_TIME, (&self.genome.repulsion).into());
-        (repulsion.and_then(|ins| match ins {
-                                SimpleInstruction::Pushi64(n) => Some(n),
-                                _ => None,
-                            }),
-         len)
+    pub fn run_repulsion(&mut self) -> Option<i64> {
+        self.execute(REPULSION).and_then(as_i64)
     }
 
     /// Runs the die chromosome. Gets a bool back that indicates whether to die or not.
-    pub fn run_die(&mut self) -> (Option<bool>, usize) {
-        let (die, len) =
-            self.machine
-                .provide_and_cycle_until(INIT_EXECUTION_TIME, (&self.genome.die).into());
-        (die.and_then(|ins| match ins {
-                          SimpleInstruction::Pushb(b) => Some(b),
-                          _ => None,
-                      }),
-         len)
+    pub fn run_die(&mut self) -> Option<bool> {
+        self.execute(DIE).and_then(as_bool)
     }
 
     /// Runs the divide chromosome. Gets a bool back that indicates whether to divide or not.
-    pub fn run_divide(&mut self) -> (Option<bool>, usize) {
-        let (divide, len) =
-            self.machine
-                .provide_and_cycle_until(INIT_EXECUTION_TIME, (&self.genome.divide).into());
-        (divide.and_then(|ins| match ins {
-                             SimpleInstruction::Pushb(b) => Some(b),
-                             _ => None,
-                         }),
-         len)
+    pub fn run_divide(&mut self) -> Option<bool> {
+        self.execute(DIVIDE).and_then(as_bool)
+    }
+
+    /// Runs the divide chromosome again directly after `run_divide`. Gets a bool back that indicates whether a
+    /// child keeps the cell's type rather than starting over at type 0.
+    pub fn run_inherit_type(&mut self) -> Option<bool> {
+        self.run_divide()
+    }
+
+    /// Runs the divide chromosome again directly after `run_inherit_type`. Gets an i64 back that indicates the
+    /// share of energy the child gets.
+    pub fn run_energy_split(&mut self) -> Option<i64> {
+        self.execute(DIVIDE).and_then(as_i64)
+    }
+
+    /// Runs the divide chromosome again directly after `run_energy_split`. Gets an i64 back that indicates the
+    /// angle the child is placed at relative to the strongest connection.
+    pub fn run_division_angle(&mut self) -> Option<i64> {
+        self.execute(DIVIDE).and_then(as_i64)
+    }
+
+    /// Runs the connection inherit chromosome for one connection. Gets an i64 back that indicates whether the
+    /// parent, the child or both keep the connection.
+    pub fn run_connection_inherit(&mut self, length: f64, cell_type: i64) -> Option<i64> {
+        self.machine.state.push_float(cell_type as f64).ok();
+        self.machine.state.push_float(length).ok();
+        self.execute(CONNECTION_INHERIT).and_then(as_i64)
+    }
+
+    /// Runs the connection kind chromosome for a newly formed connection. Gets an i64 back that indicates the kind
+    /// of connection.
+    pub fn run_connection_kind(&mut self, length: f64, cell_type: i64) -> Option<i64> {
+        self.machine.state.push_float(cell_type as f64).ok();
+        self.machine.state.push_float(length).ok();
+        self.execute(CONNECTION_KIND).and_then(as_i64)
+    }
+
+    /// Runs the emit chromosome once per chemical. Gets an i64 back for each that indicates how much to emit.
+    pub fn run_emit(&mut self) -> Vec<Option<i64>> {
+        (0..CHEMICAL_COUNT).map(|_| self.execute(EMIT).and_then(as_i64)).collect()
+    }
+
+    /// Runs a chromosome within its budget, keeping track of the cycles executed and what they cost.
+    fn execute(&mut self, chromosome: usize) -> Option<SimpleInstruction> {
+        let budget = self.genome.budget(chromosome);
+        let (ins, len) = self.machine
+            .provide_and_cycle_until(budget, self.genome.chromosomes()[chromosome].into());
+        self.cycles[chromosome] += len;
+        self.cost += len as f64 * EXECUTION_COSTS[chromosome].price;
+        ins
+    }
+
+    /// Gets the whole energy the executed cycles cost since this was last called, keeping any fraction for
+    /// the next call.
+    pub fn take_cost(&mut self) -> usize {
+        let cost = self.cost as usize;
+        self.cost -= cost as f64;
+        cost
+    }
+
+    /// Gets the number of cycles each chromosome executed since this was last called, in the order of
+    /// `CHROMOSOME_NAMES`.
+    pub fn take_cycles(&mut self) -> [usize; CHROMOSOME_COUNT] {
+        ::std::mem::replace(&mut self.cycles, [0; CHROMOSOME_COUNT])
+    }
+
+    /// Gets the length of each chromosome in the order of `CHROMOSOME_NAMES`.
+    pub fn chromosome_lengths(&self) -> [usize; CHROMOSOME_COUNT] {
+        let mut lengths = [0; CHROMOSOME_COUNT];
+        for (length, chromosome) in lengths.iter_mut().zip(self.genome.chromosomes().iter()) {
+            *length = chromosome.gene_len();
+        }
+        lengths
+    }
+
+    pub fn lambda(&self) -> f64 {
+        self.genome.lambda
+    }
+
+    /// Computes how similar the genomes of two brains are from 0 to 1.
+    pub fn relatedness(&self, other: &Brain) -> f64 {
+        self.genome.relatedness(&other.genome)
     }
 
     /// Gets the total genome size.
@@ -253,7 +523,79 @@ impl Brain {
             self.genome.connection_sever.gene_len() +
             self.genome.repulsion.gene_len() +
             self.genome.die.gene_len() +
-            self.genome.divide.gene_len()
+            self.genome.divide.gene_len() +
+            self.genome.emit.gene_len() +
+            self.genome.connection_inherit.gene_len() +
+            self.genome.connection_kind.gene_len()
+    }
+}
+
+/// Finds the genes which can affect a value yielded by the brain, along with their indices.
+///
+/// The machine's stacks persist from one chromosome run to the next, so anything a gene leaves on a stack may
+/// be read and yielded by a later run; every gene with a stack effect is live at the end of a chromosome. Only
+/// genes without any stack effect can be dropped, which is just `Nop` among the operations this crate knows.
+fn live_genes(genes: &[SimpleInstruction]) -> Vec<(usize, &SimpleInstruction)> {
+    genes.iter()
+        .enumerate()
+        .filter(|&(_, ins)| match *ins {
+                    SimpleInstruction::PlainOp(PlainOp::Nop) => false,
+                    _ => true,
+                })
+        .collect()
+}
+
+/// Computes the Levenshtein distance between two sequences of instructions.
+fn edit_distance(a: &[SimpleInstruction], b: &[SimpleInstruction]) -> usize {
+    let mut previous = (0..b.len() + 1).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ai) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, bj) in b.iter().enumerate() {
+            let substitution = previous[j] + if ai == bj { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        ::std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Genomes saved before a chromosome existed all get the same fixed one for it so loading them is reproducible.
+fn default_chromosome(len: usize, crossovers: usize) -> Chromosome {
+    Chromosome::new_rand(&mut Isaac64Rng::new_unseeded(), len, crossovers)
+}
+
+fn default_emit() -> Chromosome {
+    default_chromosome(EMIT_LEN, EMIT_CROSSOVERS)
+}
+
+fn default_connection_inherit() -> Chromosome {
+    default_chromosome(CONNECTION_INHERIT_LEN, CONNECTION_INHERIT_CROSSOVERS)
+}
+
+fn default_connection_kind() -> Chromosome {
+    default_chromosome(CONNECTION_KIND_LEN, CONNECTION_KIND_CROSSOVERS)
+}
+
+fn default_budgets() -> [usize; CHROMOSOME_COUNT] {
+    let mut budgets = [0; CHROMOSOME_COUNT];
+    for (budget, cost) in budgets.iter_mut().zip(EXECUTION_COSTS.iter()) {
+        *budget = cost.budget;
+    }
+    budgets
+}
+
+fn as_i64(ins: SimpleInstruction) -> Option<i64> {
+    match ins {
+        SimpleInstruction::Pushi64(n) => Some(n),
+        _ => None,
+    }
+}
+
+fn as_bool(ins: SimpleInstruction) -> Option<bool> {
+    match ins {
+        SimpleInstruction::Pushb(b) => Some(b),
+        _ => None,
     }
 }
 
@@ -268,3 +610,19 @@ fn int_handler() -> i64 {
 fn float_handler() -> f64 {
     0.0
 }
+
+#[cfg(test)]
+mod tests {
+    use gapush::simple::{SimpleInstruction, PlainOp};
+    use super::live_genes;
+
+    #[test]
+    fn live_genes_drop_nops_only() {
+        let genes = vec![SimpleInstruction::Pushi64(3),
+                         SimpleInstruction::PlainOp(PlainOp::Nop),
+                         SimpleInstruction::Pushb(true),
+                         SimpleInstruction::PlainOp(PlainOp::Nop)];
+        let live = live_genes(&genes).into_iter().map(|(ix, _)| ix).collect::<Vec<_>>();
+        assert_eq!(live, vec![0, 2]);
+    }
+}
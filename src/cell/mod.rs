@@ -1,32 +1,79 @@
 mod brain;
 
+pub use self::brain::{Genome, CHROMOSOME_COUNT, CHROMOSOME_NAMES};
+
 use zoom::*;
 use nalgebra as na;
 use rand::Rng;
 use gapush::simple::{SimpleInstruction, PlainOp};
 use auxillary::area_box;
+use ledger::EnergyLedger;
 use zoom::particle;
 use std::cell;
+use std::f64::consts::PI;
 
 const INIT_ENERGY: usize = 1 << 20;
 const SIZE_TO_ENERGY_RATIO: f64 = 0.05;
-const CONSUMPTION_TO_EXECUTION_RATIO: f64 = 20.0;
+/// The area of a cell per unit of energy when cells collide.
+const AREA_TO_ENERGY_RATIO: f64 = 0.0027;
 const CONSUMPTION_TO_SIZE_RATIO: f64 = 0.02;
 const CELL_SIGMOID_COEFFICIENT: f64 = 0.01;
 const STATIC_ENERGY_CONSUMPTION: usize = 1 << 9;
 
 const DRAG_COEFFICIENT: f64 = 0.1;
-const PHYSICS_DELTA: f64 = 0.005;
+/// The length of time a brain tick covers in the physics simulation.
+pub const PHYSICS_DELTA: f64 = 0.005;
 const GRAVITATE_RADIUS: f64 = 0.00001;
 
-const RANDOM_SHIFT_OFFSET: f64 = 100.0;
+/// The number of signals sent each way along a connection every cycle.
+pub const SIGNAL_CHANNELS: usize = 4;
+
+/// The number of types a cell can differentiate into; types yielded by the brain wrap around into this range.
+pub const CELL_TYPE_COUNT: i64 = 8;
+
+/// The number of diffusible chemicals cells can sense and emit.
+pub const CHEMICAL_COUNT: usize = 2;
+/// The most of a chemical a cell can emit in one cycle.
+const MAX_EMISSION: f64 = 1.0;
+const CONSUMPTION_TO_EMISSION_RATIO: f64 = 256.0;
+
+const DIVISION_SHIFT_OFFSET: f64 = 100.0;
 const SEPARATION_THRESHOLD: f64 = 900.0;
+/// The stiffness of rod connections, ten times that of the stiffest spring (`HOOKE_STATIC + HOOKE_DYNAMIC`).
+/// Two cells of unit inertia joined by a rod stay stable with semi-implicit Euler while
+/// `2 * ROD_HOOKE * (PHYSICS_DELTA / PHYSICS_SUBSTEPS)^2 < 4`, so this is far from needing more substeps.
+pub const ROD_HOOKE: f64 = 1.1;
+/// How a cell divides when its brain doesn't decide: the child keeps the type, gets half the energy and is
+/// placed at a random angle.
+pub const DEFAULT_DIVISION: (bool, f64, Option<f64>) = (true, 0.5, None);
+/// The longest rest length a cell can choose for a connection.
+const MAX_REST_LENGTH: f64 = 200.0;
+/// The rest lengths of each kind of connection when neither cell chooses one.
+const SPRING_REST_LENGTH: f64 = 0.0;
+const ROD_REST_LENGTH: f64 = 100.0;
+const ROPE_REST_LENGTH: f64 = 100.0;
+/// How getting older affects cells.
+const AGING: Aging = Aging::None;
+
+/// How getting older affects a cell. Cells always know their age, but it only costs them under some models.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Aging {
+    /// Age has no effect.
+    None,
+    /// The static energy consumption grows by this fraction of itself for every tick of age.
+    RisingCost(f64),
+    /// Children are mutated once more for every this many ticks of their parent's age.
+    RisingMutation(usize),
+}
 
 #[derive(Clone)]
 pub struct Cell {
     energy: usize,
     particle: particle::BasicParticle<na::Vector2<f64>, f64>,
     brain: brain::Brain,
+    cell_type: i64,
+    /// The number of ticks the cell has been alive for.
+    age: usize,
     closest_distance_squared: cell::Cell<Option<f64>>,
 }
 
@@ -38,10 +85,47 @@ impl Cell {
             energy: INIT_ENERGY,
             particle: particle,
             brain: brain::Brain::new_rand(energy_to_size(INIT_ENERGY), rng),
+            cell_type: 0,
+            age: 0,
             closest_distance_squared: cell::Cell::new(None),
         }
     }
 
+    pub fn new(genome: Genome, particle: particle::BasicParticle<na::Vector2<f64>, f64>) -> Cell {
+        Cell {
+            energy: INIT_ENERGY,
+            particle: particle,
+            brain: brain::Brain::new(genome, energy_to_size(INIT_ENERGY)),
+            cell_type: 0,
+            age: 0,
+            closest_distance_squared: cell::Cell::new(None),
+        }
+    }
+
+    pub fn genome(&self) -> &Genome {
+        self.brain.genome()
+    }
+
+    pub fn age(&self) -> usize {
+        self.age
+    }
+
+    /// Makes the cell newborn, which is done to children after division so they don't carry over their
+    /// parent's age or executed cycles.
+    pub fn make_newborn(&mut self) {
+        self.age = 0;
+        self.brain.take_cycles();
+    }
+
+    /// Gets the type the cell has differentiated into.
+    pub fn cell_type(&self) -> i64 {
+        self.cell_type
+    }
+
+    pub fn set_cell_type(&mut self, cell_type: i64) {
+        self.cell_type = ((cell_type % CELL_TYPE_COUNT) + CELL_TYPE_COUNT) % CELL_TYPE_COUNT;
+    }
+
     pub fn set_energy(&mut self, energy: usize) {
         self.energy = energy;
         self.brain.set_size(energy_to_size(energy));
@@ -50,6 +134,20 @@ impl Cell {
     pub fn energy(&self) -> usize {
         self.energy
     }
+    /// Gets the length of each chromosome in the order of `CHROMOSOME_NAMES`.
+    pub fn chromosome_lengths(&self) -> [usize; CHROMOSOME_COUNT] {
+        self.brain.chromosome_lengths()
+    }
+
+    pub fn lambda(&self) -> f64 {
+        self.brain.lambda()
+    }
+
+    /// Computes how similar the genomes of two cells are from 0 (nothing in common) to 1 (identical).
+    pub fn relatedness(&self, other: &Cell) -> f64 {
+        self.brain.relatedness(&other.brain)
+    }
+
     pub fn closest_distance_squared(&self) -> Option<f64> {
         let cds = self.closest_distance_squared.get().clone();
         self.closest_distance_squared.set(None);
@@ -57,12 +155,14 @@ impl Cell {
     }
 
     pub fn create_state(&self,
+                        chemicals: Vec<f64>,
                         out_connections: Vec<ConnectionState>,
                         in_connections: Vec<ConnectionState>)
                         -> StateParameters {
         StateParameters {
             position: self.particle.position,
             energy: self.energy,
+            chemicals: chemicals,
             out_connections: out_connections,
             in_connections: in_connections,
         }
@@ -70,50 +170,66 @@ impl Cell {
 
     pub fn run_connection(&mut self,
                           connection_states: Vec<ConnectionState>)
-                          -> (Vec<ConnectionDelta>, usize) {
+                          -> Vec<ConnectionDelta> {
         connection_states
             .into_iter()
-            .map(|cs| (cs.length, self.brain.run_connection(cs.length, cs.incoming)))
-            .map(|(length, (elasticity, signal, sever, cycles))| {
+            .map(|cs| (cs.length, self.brain.run_connection(cs.length, cs.cell_type, cs.incoming)))
+            .map(|(length, (elasticity, rest_length, signals, sever))| {
                      (cell_sigmoid(elasticity.unwrap_or(0)),
-                      signal.unwrap_or(SimpleInstruction::PlainOp(PlainOp::Nop)),
-                      sever.unwrap_or(false) || length > SEPARATION_THRESHOLD,
-                      cycles)
+                      rest_length.map(|n| MAX_REST_LENGTH * cell_sigmoid(n)),
+                      signals.into_iter()
+                          .map(|signal| signal.unwrap_or(SimpleInstruction::PlainOp(PlainOp::Nop)))
+                          .collect::<Vec<_>>(),
+                      sever.unwrap_or(false) || length > SEPARATION_THRESHOLD)
                  })
-            .map(|(elasticity, signal, sever, cycles)| {
-                     (ConnectionDelta {
-                          elasticity: elasticity,
-                          signal: signal,
-                          sever: sever,
-                      },
-                      cycles)
+            .map(|(elasticity, rest_length, signals, sever)| {
+                     ConnectionDelta {
+                         elasticity: elasticity,
+                         rest_length: rest_length,
+                         signals: signals,
+                         sever: sever,
+                     }
                  })
-            .fold((Vec::new(), 0), |(mut v, tcycles), (delta, cycles)| {
-                v.push(delta);
-                (v, tcycles + cycles)
-            })
+            .collect()
     }
 
     pub fn cycle(&mut self, state: StateParameters) -> Delta {
-        let cycle_cycles = self.brain.run_cycle(state.energy as f64);
-        let (out_connection_deltas, out_connection_cycles) =
-            self.run_connection(state.out_connections);
-        let (in_connection_deltas, in_connection_cycles) =
-            self.run_connection(state.in_connections);
-        let (repulsion, repulsion_cycles) = self.brain.run_repulsion();
-        let repulsion = cell_sigmoid(repulsion.unwrap_or(0));
-        let (die, die_cycles) = self.brain.run_die();
-        let die = die.unwrap_or(false);
-        let (divide, divide_cycles) = self.brain.run_divide();
-        let divide = divide.unwrap_or(false);
-        self.energy = self.energy
-            .checked_sub(STATIC_ENERGY_CONSUMPTION +
-                            (CONSUMPTION_TO_SIZE_RATIO * self.brain.total_size() as f64) as usize +
-                            (CONSUMPTION_TO_EXECUTION_RATIO *
-                          (cycle_cycles + out_connection_cycles + in_connection_cycles +
-                           repulsion_cycles + die_cycles +
-                           divide_cycles) as f64) as usize)
-            .unwrap_or(0);
+        let cell_type =
+            self.brain.run_cycle(self.cell_type, self.age as f64, state.energy as f64, &state.chemicals);
+        if let Some(cell_type) = cell_type {
+            self.set_cell_type(cell_type);
+        }
+        let out_connection_deltas = self.run_connection(state.out_connections);
+        let in_connection_deltas = self.run_connection(state.in_connections);
+        let repulsion = cell_sigmoid(self.brain.run_repulsion().unwrap_or(0));
+        let die = self.brain.run_die().unwrap_or(false);
+        let divide = self.brain.run_divide().unwrap_or(false);
+        // The shape of the division is only decided when the cell is going to divide.
+        let (inherit_type, energy_split, division_angle) = if divide {
+            let inherit_type = self.brain.run_inherit_type();
+            let energy_split = self.brain.run_energy_split();
+            let division_angle = self.brain.run_division_angle();
+            (inherit_type.unwrap_or(DEFAULT_DIVISION.0),
+             energy_split.map(cell_sigmoid).unwrap_or(DEFAULT_DIVISION.1),
+             division_angle.map(|n| 2.0 * PI * cell_sigmoid(n)).or(DEFAULT_DIVISION.2))
+        } else {
+            DEFAULT_DIVISION
+        };
+        let emissions = self.brain.run_emit();
+        let emissions = emissions.into_iter()
+            .map(|emission| MAX_EMISSION * cell_sigmoid(emission.unwrap_or(0)))
+            .collect::<Vec<_>>();
+        let mut spent = EnergyLedger::default();
+        let basal = match AGING {
+            Aging::RisingCost(rate) => (STATIC_ENERGY_CONSUMPTION as f64 * (1.0 + rate * self.age as f64)) as usize,
+            _ => STATIC_ENERGY_CONSUMPTION,
+        };
+        spent.basal = self.consume(basal);
+        spent.emission = self.consume((CONSUMPTION_TO_EMISSION_RATIO * emissions.iter().sum::<f64>()) as usize);
+        spent.size = self.consume((CONSUMPTION_TO_SIZE_RATIO * self.brain.total_size() as f64) as usize);
+        let execution = self.brain.take_cost();
+        spent.execution = self.consume(execution);
+        self.age += 1;
         Delta {
             out_connections: out_connection_deltas,
             in_connections: in_connection_deltas,
@@ -121,27 +237,102 @@ impl Cell {
             // Also consider that an energy of 0 indicates death.
             die: die || self.energy == 0,
             divide: divide,
+            inherit_type: inherit_type,
+            energy_split: energy_split,
+            division_angle: division_angle,
+            emissions: emissions,
+            spent: spent,
+            cycles: self.brain.take_cycles(),
+        }
+    }
+
+    /// Takes as much of an amount of energy as the cell has and gives back how much was taken.
+    fn consume(&mut self, amount: usize) -> usize {
+        let spent = amount.min(self.energy);
+        self.energy -= spent;
+        spent
+    }
+
+    /// Decides which of the cells keeps each connection when this cell divides.
+    /// The energy for running the brain is consumed immediately.
+    pub fn run_connection_inheritance(&mut self,
+                                      connection_states: Vec<ConnectionState>)
+                                      -> Vec<ConnectionInheritance> {
+        let inheritances = connection_states.into_iter()
+            .map(|cs| {
+                     let inherit = self.brain.run_connection_inherit(cs.length, cs.cell_type);
+                     match inherit.map(|n| ((n % 3) + 3) % 3) {
+                         Some(1) => ConnectionInheritance::Parent,
+                         Some(2) => ConnectionInheritance::Child,
+                         _ => ConnectionInheritance::Both,
+                     }
+                 })
+            .collect();
+        let execution = self.brain.take_cost();
+        self.consume(execution);
+        inheritances
+    }
+
+    /// Decides the kind of a newly formed connection.
+    /// The energy for running the brain is consumed immediately.
+    pub fn run_connection_kind(&mut self, connection_state: ConnectionState) -> ConnectionKind {
+        let kind = self.brain.run_connection_kind(connection_state.length, connection_state.cell_type);
+        let execution = self.brain.take_cost();
+        self.consume(execution);
+        match kind.map(|n| ((n % 4) + 4) % 4) {
+            Some(1) => ConnectionKind::Rod,
+            Some(2) => ConnectionKind::Rope,
+            Some(3) => ConnectionKind::Signal,
+            _ => ConnectionKind::Spring,
         }
     }
 
     pub fn mutate<R: Rng>(&mut self, rng: &mut R) {
-        self.brain.mutate(rng);
+        let times = match AGING {
+            Aging::RisingMutation(ticks) => 1 + self.age / ticks,
+            _ => 1,
+        };
+        for _ in 0..times {
+            self.brain.mutate(rng);
+        }
     }
 
-    pub fn random_shift<R: Rng>(&mut self, rng: &mut R) {
-        use std::f64::consts::PI;
-        let rand_angle = rng.next_f64() * PI * 2.0;
-        let shift = na::Vector2::new(RANDOM_SHIFT_OFFSET * rand_angle.cos(),
-            RANDOM_SHIFT_OFFSET * rand_angle.sin());
+    /// Moves the cell by the division offset in the direction of an angle in radians.
+    pub fn shift(&mut self, angle: f64) {
+        let shift = na::Vector2::new(DIVISION_SHIFT_OFFSET * angle.cos(),
+            DIVISION_SHIFT_OFFSET * angle.sin());
         self.particle.position = area_box().wrap_position(self.particle.position + shift);
     }
 
-    pub fn update_physics(&mut self) {
+    /// Applies drag and advances the particle with semi-implicit Euler.
+    pub fn update_physics(&mut self, delta: f64) {
         self.particle.drag(DRAG_COEFFICIENT);
-        self.particle.advance(PHYSICS_DELTA);
+        self.particle.advance(delta);
         self.particle.position = area_box().wrap_position(self.particle.position);
     }
 
+    /// Applies drag and consumes every impulse since the last advance, giving the resulting acceleration
+    /// without moving the particle.
+    ///
+    /// zoom doesn't expose the accumulated impulse, so this relies on `BasicParticle::advance(dt)` adding
+    /// `impulse / inertia * dt` to the velocity and clearing the impulse. zoom is pinned to an exact version in
+    /// `Cargo.toml` for this; check this still holds before updating it.
+    pub fn take_acceleration(&mut self) -> na::Vector2<f64> {
+        self.particle.drag(DRAG_COEFFICIENT);
+        let (position, velocity) = (self.particle.position, self.particle.velocity);
+        // Advancing by a unit of time leaves the acceleration in the change of velocity.
+        self.particle.advance(1.0);
+        let acceleration = self.particle.velocity - velocity;
+        self.particle.position = position;
+        self.particle.velocity = velocity;
+        acceleration
+    }
+
+    pub fn set_motion(&mut self, position: na::Vector2<f64>, velocity: na::Vector2<f64>) {
+        self.particle.position = area_box().wrap_position(position);
+        self.particle.velocity = velocity;
+    }
+
     pub fn impulse(&self, v: na::Vector2<f64>) {
         self.particle.impulse(&v);
     }
@@ -150,11 +341,42 @@ impl Cell {
         self.particle.position.clone()
     }
 
-    pub fn interact_connection(&self, other: &Self, hooke: f64) {
-        particle::hooke_delta(&self.particle, &other.particle, hooke, |(from, to)| area_box().wrap_delta(to - from));
+    /// The radius of the cell as a hard body.
+    pub fn radius(&self) -> f64 {
+        energy_to_radius(self.energy)
     }
 
-    pub fn interact_repel(&self, other: &Self, newton: f64) {
+    pub fn velocity(&self) -> na::Vector2<f64> {
+        self.particle.velocity.clone()
+    }
+
+    /// Applies the force of a connection of the given kind; rods ignore `hooke` and use their own stiffness.
+    pub fn interact_connection(&self, other: &Self, kind: ConnectionKind, hooke: f64, rest_length: f64) {
+        match kind {
+            ConnectionKind::Spring => self.pull(other, hooke, rest_length, true),
+            ConnectionKind::Rod => self.pull(other, ROD_HOOKE, rest_length, true),
+            ConnectionKind::Rope => self.pull(other, hooke, rest_length, false),
+            ConnectionKind::Signal => {}
+        }
+    }
+
+    /// Pulls both cells towards the rest length like a spring, only pushing them apart if `push` is set.
+    fn pull(&self, other: &Self, hooke: f64, rest_length: f64, push: bool) {
+        use nalgebra::Norm;
+        let delta = area_box().wrap_delta(other.particle.position - self.particle.position);
+        let length = delta.norm();
+        let stretch = length - rest_length;
+        if length == 0.0 || (stretch < 0.0 && !push) {
+            return;
+        }
+        let force = delta * (hooke * stretch / length);
+        self.particle.impulse(&force);
+        other.particle.impulse(&-force);
+    }
+
+    /// Records the distance to another cell on both cells if it is the closest either has seen since the last
+    /// call to `closest_distance_squared`.
+    pub fn observe_neighbor(&self, other: &Self) {
         use nalgebra::Norm;
         let distance_squared = area_box().wrap_delta(self.particle.position - other.particle.position).norm_squared();
         if self.closest_distance_squared.get().map(|d| d > distance_squared).unwrap_or(true) {
@@ -163,6 +385,9 @@ impl Cell {
         if other.closest_distance_squared.get().map(|d| d > distance_squared).unwrap_or(true) {
             other.closest_distance_squared.set(Some(distance_squared));
         }
+    }
+
+    pub fn interact_repel(&self, other: &Self, newton: f64) {
         particle::gravitate_radius_squared_delta(&self.particle, &other.particle,
                 GRAVITATE_RADIUS * GRAVITATE_RADIUS,
                 -newton,
@@ -172,14 +397,83 @@ impl Cell {
 
 #[derive(Clone, Debug)]
 pub struct ConnectionState {
-    pub incoming: SimpleInstruction,
+    /// The signal on each channel sent by the other cell.
+    pub incoming: Vec<SimpleInstruction>,
     pub length: f64,
+    /// The type of the other cell.
+    pub cell_type: i64,
+}
+
+/// Which cells keep a connection when a cell divides.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionInheritance {
+    /// Only the parent keeps the connection.
+    Parent,
+    /// The connection moves to the child.
+    Child,
+    /// The parent keeps the connection and the child gets a new one to the same cell.
+    Both,
+}
+
+/// How a connection moves the cells it joins.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionKind {
+    /// Pulls the cells together with a stiffness that depends on the elasticity both cells choose.
+    Spring,
+    /// Stiffly holds the cells at the rest length.
+    Rod,
+    /// Pulls the cells together once they are further apart than the rest length, but never pushes them.
+    Rope,
+    /// Only carries signals and exerts no force.
+    Signal,
+}
+
+impl ConnectionKind {
+    /// The rest length used when neither cell chooses one.
+    pub fn default_rest_length(self) -> f64 {
+        match self {
+            ConnectionKind::Spring | ConnectionKind::Signal => SPRING_REST_LENGTH,
+            ConnectionKind::Rod => ROD_REST_LENGTH,
+            ConnectionKind::Rope => ROPE_REST_LENGTH,
+        }
+    }
+}
+
+impl Default for ConnectionKind {
+    fn default() -> ConnectionKind {
+        ConnectionKind::Spring
+    }
+}
+
+/// The weight of an edge in the cell graph.
+#[derive(Clone, Debug, Default)]
+pub struct Connection {
+    /// Chosen when the connection is formed and kept for its lifetime.
+    pub kind: ConnectionKind,
+    /// The delta of the cell the edge leaves.
+    pub source: ConnectionDelta,
+    /// The delta of the cell the edge enters.
+    pub target: ConnectionDelta,
+}
+
+impl Connection {
+    /// The mean of the rest lengths the cells chose, or the one chosen if only one cell chose, or the default
+    /// for the kind if neither did.
+    pub fn rest_length(&self) -> f64 {
+        match (self.source.rest_length, self.target.rest_length) {
+            (Some(a), Some(b)) => (a + b) * 0.5,
+            (Some(l), None) | (None, Some(l)) => l,
+            (None, None) => self.kind.default_rest_length(),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct StateParameters {
     pub position: na::Vector2<f64>,
     pub energy: usize,
+    /// The concentration of each chemical at the cell.
+    pub chemicals: Vec<f64>,
     pub out_connections: Vec<ConnectionState>,
     pub in_connections: Vec<ConnectionState>,
 }
@@ -187,7 +481,10 @@ pub struct StateParameters {
 #[derive(Clone, Debug)]
 pub struct ConnectionDelta {
     pub elasticity: f64,
-    pub signal: SimpleInstruction,
+    /// The length the connection relaxes to, if the cell chose one.
+    pub rest_length: Option<f64>,
+    /// The signal sent on each channel.
+    pub signals: Vec<SimpleInstruction>,
     pub sever: bool,
 }
 
@@ -195,7 +492,8 @@ impl Default for ConnectionDelta {
     fn default() -> ConnectionDelta {
         ConnectionDelta {
             elasticity: 0.5,
-            signal: SimpleInstruction::PlainOp(PlainOp::Nop),
+            rest_length: None,
+            signals: vec![SimpleInstruction::PlainOp(PlainOp::Nop); SIGNAL_CHANNELS],
             sever: false,
         }
     }
@@ -208,12 +506,29 @@ pub struct Delta {
     pub repulsion: f64,
     pub die: bool,
     pub divide: bool,
+    /// Whether a child from dividing keeps this cell's type rather than starting over at type 0.
+    pub inherit_type: bool,
+    /// The fraction of this cell's energy a child from dividing gets.
+    pub energy_split: f64,
+    /// The angle in radians from the strongest connection at which a child from dividing is placed, or `None`
+    /// to place it at a random angle.
+    pub division_angle: Option<f64>,
+    /// How much of each chemical the cell emits.
+    pub emissions: Vec<f64>,
+    /// The energy the cell spent this tick.
+    pub spent: EnergyLedger,
+    /// The cycles each chromosome executed this tick in the order of `CHROMOSOME_NAMES`.
+    pub cycles: [usize; CHROMOSOME_COUNT],
 }
 
 fn energy_to_size(energy: usize) -> usize {
     (energy as f64 * SIZE_TO_ENERGY_RATIO) as usize
 }
 
+fn energy_to_radius(energy: usize) -> f64 {
+    (energy as f64 * AREA_TO_ENERGY_RATIO / PI).sqrt()
+}
+
 fn cell_sigmoid(n: i64) -> f64 {
     let t = n as f64 * CELL_SIGMOID_COEFFICIENT;
     1.0 / (1.0 + (-t).exp())
@@ -0,0 +1,111 @@
+use nalgebra::Vector2;
+use auxillary::area_box;
+use cell::CHEMICAL_COUNT;
+use obstacle::Scenario;
+
+/// The number of grid squares along each side of the world.
+const CHEMICAL_GRID_SIZE: usize = 64;
+
+/// How a chemical spreads and breaks down over time.
+#[derive(Copy, Clone, Debug)]
+pub struct ChemicalProperties {
+    /// The fraction of the difference with its neighbors a square moves towards each tick; keep it below 0.25.
+    pub diffusion: f64,
+    /// The fraction of the chemical which decays each tick.
+    pub decay: f64,
+}
+
+const CHEMICALS: [ChemicalProperties; CHEMICAL_COUNT] = [ChemicalProperties {
+                                                             diffusion: 0.2,
+                                                             decay: 0.01,
+                                                         },
+                                                         ChemicalProperties {
+                                                             diffusion: 0.05,
+                                                             decay: 0.001,
+                                                         }];
+
+/// Concentrations of every chemical over a grid covering the world, which wraps around like the world does.
+pub struct ChemicalField {
+    concentrations: Vec<Vec<f64>>,
+    scratch: Vec<f64>,
+    /// Whether chemicals pass between each square and its neighbors in the positive x and y directions.
+    open: Vec<[bool; 2]>,
+}
+
+impl ChemicalField {
+    /// Creates an empty field where chemicals don't diffuse into squares covered by obstacles or across walls.
+    pub fn new(scenario: &Scenario) -> ChemicalField {
+        let n = CHEMICAL_GRID_SIZE;
+        let open = (0..n * n)
+            .map(|ix| {
+                let (x, y) = (ix % n, ix / n);
+                let passes = |nx: usize, ny: usize| {
+                    let (from, to) = (square_center(x, y), square_center(nx % n, ny % n));
+                    !scenario.covers(from) && !scenario.covers(to) && !scenario.blocks(from, to)
+                };
+                [passes(x + 1, y), passes(x, y + 1)]
+            })
+            .collect();
+        ChemicalField {
+            concentrations: vec![vec![0.0; n * n]; CHEMICAL_COUNT],
+            scratch: vec![0.0; n * n],
+            open: open,
+        }
+    }
+
+    /// Gets the concentration of each chemical at a position.
+    pub fn sample(&self, position: Vector2<f64>) -> Vec<f64> {
+        let ix = grid_index(position);
+        self.concentrations.iter().map(|c| c[ix]).collect()
+    }
+
+    /// Adds an amount of each chemical at a position.
+    pub fn emit(&mut self, position: Vector2<f64>, amounts: &[f64]) {
+        let ix = grid_index(position);
+        for (c, &amount) in self.concentrations.iter_mut().zip(amounts) {
+            c[ix] += amount;
+        }
+    }
+
+    /// Diffuses and decays every chemical by one tick.
+    pub fn advance(&mut self) {
+        let n = CHEMICAL_GRID_SIZE;
+        for (c, properties) in self.concentrations.iter_mut().zip(CHEMICALS.iter()) {
+            for y in 0..n {
+                for x in 0..n {
+                    let (left, down) = (y * n + (x + n - 1) % n, ((y + n - 1) % n) * n + x);
+                    let (right, up) = (y * n + (x + 1) % n, ((y + 1) % n) * n + x);
+                    let ix = y * n + x;
+                    let center = c[ix];
+                    // Only sides without an obstacle between the squares exchange chemicals.
+                    let laplacian = [(self.open[ix][0], right),
+                                     (self.open[left][0], left),
+                                     (self.open[ix][1], up),
+                                     (self.open[down][1], down)]
+                        .iter()
+                        .filter(|&&(open, _)| open)
+                        .map(|&(_, neighbor)| c[neighbor] - center)
+                        .sum::<f64>();
+                    self.scratch[ix] = (center + properties.diffusion * laplacian) * (1.0 - properties.decay);
+                }
+            }
+            c.copy_from_slice(&self.scratch);
+        }
+    }
+}
+
+fn square_center(x: usize, y: usize) -> Vector2<f64> {
+    let area = area_box();
+    let size = area.offset * (2.0 / CHEMICAL_GRID_SIZE as f64);
+    area.origin - area.offset + Vector2::new((x as f64 + 0.5) * size.x, (y as f64 + 0.5) * size.y)
+}
+
+fn grid_index(position: Vector2<f64>) -> usize {
+    let area = area_box();
+    let cell_of = |p: f64, origin: f64, offset: f64| {
+        let t = (p - origin + offset) / (2.0 * offset);
+        ((t * CHEMICAL_GRID_SIZE as f64).floor() as isize).max(0).min(CHEMICAL_GRID_SIZE as isize - 1) as usize
+    };
+    cell_of(position.y, area.origin.y, area.offset.y) * CHEMICAL_GRID_SIZE +
+    cell_of(position.x, area.origin.x, area.offset.x)
+}
@@ -0,0 +1,83 @@
+use CellContainer;
+use CellGraph;
+
+const UNIFORM_CELL_COLOR: [f32; 4] = [0.0, 1.0, 1.0, 1.0];
+const LOW_ENERGY_COLOR: [f32; 4] = [0.0, 0.2, 1.0, 1.0];
+const HIGH_ENERGY_COLOR: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
+const UNASSIGNED_SPECIES_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+pub const EDGE_COLOR: [f32; 4] = [0.0, 0.35, 0.0, 1.0];
+
+/// Determines how cells are colored by every renderer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Every cell is the same color.
+    Uniform,
+    /// Cells are colored on a gradient from the lowest to the highest energy cell.
+    Energy,
+    /// Each species gets its own color. Cells which haven't been assigned a species yet are grey.
+    Species,
+}
+
+/// Colors cells according to a `ColorMode` using the state of the whole graph.
+pub struct Palette {
+    mode: ColorMode,
+    min_energy: usize,
+    max_energy: usize,
+}
+
+impl Palette {
+    pub fn new(mode: ColorMode, graph: &CellGraph) -> Palette {
+        let energies = graph.node_indices().map(|nix| graph[nix].cell.energy());
+        let (min_energy, max_energy) =
+            energies.fold((usize::max_value(), 0), |(min, max), e| (min.min(e), max.max(e)));
+        Palette {
+            mode: mode,
+            min_energy: min_energy,
+            max_energy: max_energy,
+        }
+    }
+
+    pub fn cell_color(&self, cc: &CellContainer) -> [f32; 4] {
+        match self.mode {
+            ColorMode::Uniform => UNIFORM_CELL_COLOR,
+            ColorMode::Energy => {
+                let t = if self.max_energy > self.min_energy {
+                    (cc.cell.energy() - self.min_energy) as f32 /
+                    (self.max_energy - self.min_energy) as f32
+                } else {
+                    1.0
+                };
+                let mut color = [0.0; 4];
+                for c in 0..4 {
+                    color[c] = LOW_ENERGY_COLOR[c] + (HIGH_ENERGY_COLOR[c] - LOW_ENERGY_COLOR[c]) * t;
+                }
+                color
+            }
+            ColorMode::Species => cc.species.map(species_color).unwrap_or(UNASSIGNED_SPECIES_COLOR),
+        }
+    }
+}
+
+/// Picks a bright color for a species by stepping the hue by the golden ratio so nearby ids differ.
+fn species_color(id: usize) -> [f32; 4] {
+    let hue = (id as f32 * 0.618034).fract() * 6.0;
+    let x = 1.0 - (hue % 2.0 - 1.0).abs();
+    let (r, g, b) = match hue as usize {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    [r, g, b, 1.0]
+}
+
+/// Formats a color as an SVG/CSS hex color, ignoring alpha.
+pub fn hex_color(color: [f32; 4]) -> String {
+    let channel = |c: f32| (c.max(0.0).min(1.0) * 255.0) as u8;
+    format!("#{:02x}{:02x}{:02x}",
+            channel(color[0]),
+            channel(color[1]),
+            channel(color[2]))
+}
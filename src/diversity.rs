@@ -0,0 +1,80 @@
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use rand::Rng;
+use cell::CHROMOSOME_COUNT;
+use CellGraph;
+
+/// The most pairs of cells sampled when computing the mean distance between chromosomes.
+const DISTANCE_SAMPLES: usize = 64;
+
+/// Measures of how genetically varied the population is.
+#[derive(Clone, Debug, Default)]
+pub struct Diversity {
+    /// The mean edit distance between the same chromosome of two cells, for each chromosome.
+    pub mean_distances: [f64; CHROMOSOME_COUNT],
+    /// The number of different genomes in the population.
+    pub distinct_genotypes: usize,
+    /// The Shannon entropy in bits of the instruction frequencies across the population, for each chromosome.
+    pub entropies: [f64; CHROMOSOME_COUNT],
+}
+
+impl Diversity {
+    pub fn measure<R: Rng>(graph: &CellGraph, rng: &mut R) -> Diversity {
+        let nodes = graph.node_indices().collect::<Vec<_>>();
+        let mut diversity = Diversity::default();
+
+        // Compute all the pairs in small populations and sample them in large ones.
+        let pairs = if nodes.len() < 2 {
+            Vec::new()
+        } else if nodes.len() * (nodes.len() - 1) / 2 <= DISTANCE_SAMPLES {
+            (0..nodes.len())
+                .flat_map(|i| (i + 1..nodes.len()).map(move |j| (i, j)))
+                .collect()
+        } else {
+            (0..DISTANCE_SAMPLES)
+                .map(|_| {
+                         let i = rng.gen_range(0, nodes.len());
+                         (i, (i + rng.gen_range(1, nodes.len())) % nodes.len())
+                     })
+                .collect::<Vec<_>>()
+        };
+        for &(i, j) in &pairs {
+            let distances = graph[nodes[i]]
+                .cell
+                .genome()
+                .chromosome_distances(graph[nodes[j]].cell.genome());
+            for (mean, &distance) in diversity.mean_distances.iter_mut().zip(distances.iter()) {
+                *mean += distance as f64 / pairs.len() as f64;
+            }
+        }
+
+        diversity.distinct_genotypes = nodes.iter()
+            .map(|&nix| {
+                     let mut hasher = DefaultHasher::new();
+                     format!("{:?}", graph[nix].cell.genome()).hash(&mut hasher);
+                     hasher.finish()
+                 })
+            .collect::<HashSet<_>>()
+            .len();
+
+        let mut frequencies: Vec<HashMap<String, usize>> = vec![HashMap::new(); CHROMOSOME_COUNT];
+        for &nix in &nodes {
+            for (counts, genes) in frequencies.iter_mut().zip(graph[nix].cell.genome().chromosome_genes().iter()) {
+                for ins in genes.iter() {
+                    *counts.entry(format!("{:?}", ins)).or_insert(0) += 1;
+                }
+            }
+        }
+        for (entropy, counts) in diversity.entropies.iter_mut().zip(frequencies.iter()) {
+            let total = counts.values().sum::<usize>() as f64;
+            *entropy = -counts.values()
+                .map(|&count| {
+                         let p = count as f64 / total;
+                         p * p.log2()
+                     })
+                .sum::<f64>();
+        }
+        diversity
+    }
+}
@@ -0,0 +1,139 @@
+use std::f64::consts::PI;
+use nalgebra::Vector2;
+use num::Zero;
+use zoom::Toroid;
+use auxillary::area_box;
+use CellGraph;
+
+/// A current in the fluid the cells live in, which accelerates every cell it covers.
+#[derive(Copy, Clone, Debug)]
+pub enum Flow {
+    /// Pushes every cell the same way everywhere.
+    Drift { x: f64, y: f64 },
+    /// Pushes cells in a horizontal band through the center sideways, one way above the center and the other
+    /// way below, harder the further they are from it.
+    ShearBand {
+        /// The half-height of the band as a fraction of the half-height of the world.
+        ratio: f64,
+        /// The acceleration at the edges of the band.
+        acceleration: f64,
+    },
+    /// Swirls cells around a point, counterclockwise for a positive strength, fading out with distance.
+    Vortex {
+        x: f64,
+        y: f64,
+        /// The distance at which the swirl has faded to about a third.
+        radius: f64,
+        strength: f64,
+    },
+    /// Pushes cells around with smooth Perlin noise which slowly drifts over time.
+    Turbulence {
+        /// The rough size of the eddies.
+        scale: f64,
+        strength: f64,
+        /// How many noise lattice squares the noise drifts by each tick.
+        speed: f64,
+        seed: u64,
+    },
+    /// Scales another flow by a sine wave, so it periodically weakens and reverses.
+    Oscillating {
+        flow: &'static Flow,
+        /// The number of ticks in a full cycle.
+        period: f64,
+    },
+}
+
+impl Flow {
+    /// The acceleration the flow applies at a position on a tick.
+    pub fn acceleration(&self, position: Vector2<f64>, tick: usize) -> Vector2<f64> {
+        let area = area_box();
+        match *self {
+            Flow::Drift { x, y } => Vector2::new(x, y),
+            Flow::ShearBand { ratio, acceleration } => {
+                let y = position.y - area.origin.y;
+                if y.abs() < area.offset.y * ratio {
+                    Vector2::new(y / (area.offset.y * ratio) * acceleration, 0.0)
+                } else {
+                    Vector2::zero()
+                }
+            }
+            Flow::Vortex { x, y, radius, strength } => {
+                let delta = area.wrap_delta(position - Vector2::new(x, y));
+                let falloff = (-(delta.x * delta.x + delta.y * delta.y) / (radius * radius)).exp();
+                Vector2::new(-delta.y, delta.x) * (strength * falloff / radius)
+            }
+            Flow::Turbulence { scale, strength, speed, seed } => {
+                // The lattice repeats once across the world so the noise wraps around with it.
+                let periods = ((2.0 * area.offset.x / scale).round().max(1.0) as i64,
+                               (2.0 * area.offset.y / scale).round().max(1.0) as i64);
+                let x = (position.x - area.origin.x + area.offset.x) / (2.0 * area.offset.x) * periods.0 as f64 +
+                        tick as f64 * speed;
+                let y = (position.y - area.origin.y + area.offset.y) / (2.0 * area.offset.y) * periods.1 as f64;
+                Vector2::new(perlin(x, y, periods, seed), perlin(x, y, periods, seed ^ 1)) * strength
+            }
+            Flow::Oscillating { flow, period } => {
+                flow.acceleration(position, tick) * (2.0 * PI * tick as f64 / period).sin()
+            }
+        }
+    }
+}
+
+/// Applies a set of flows, added together, to every cell.
+pub struct FlowField {
+    flows: &'static [Flow],
+    tick: usize,
+}
+
+impl FlowField {
+    pub fn new(flows: &'static [Flow]) -> FlowField {
+        FlowField {
+            flows: flows,
+            tick: 0,
+        }
+    }
+
+    pub fn apply(&self, graph: &CellGraph) {
+        for nix in graph.node_indices() {
+            let position = graph[nix].cell.position();
+            let acceleration = self.flows
+                .iter()
+                .fold(Vector2::zero(), |total, flow| total + flow.acceleration(position, self.tick));
+            graph[nix].cell.impulse(acceleration);
+        }
+    }
+
+    /// Moves time-varying flows on by a tick.
+    pub fn advance(&mut self) {
+        self.tick += 1;
+    }
+}
+
+/// Perlin noise from -1 to 1 over a lattice which repeats after the given number of squares on each axis.
+fn perlin(x: f64, y: f64, periods: (i64, i64), seed: u64) -> f64 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (ix, iy) = (x0 as i64, y0 as i64);
+    let corner = |dx: i64, dy: i64| {
+        let h = hash(wrap(ix + dx, periods.0), wrap(iy + dy, periods.1), seed);
+        let angle = h as f64 / u64::max_value() as f64 * 2.0 * PI;
+        angle.cos() * (fx - dx as f64) + angle.sin() * (fy - dy as f64)
+    };
+    let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+    let (u, v) = (fade(fx), fade(fy));
+    // Gradient noise peaks at about 0.7 in two dimensions.
+    lerp(lerp(corner(0, 0), corner(1, 0), u),
+         lerp(corner(0, 1), corner(1, 1), u),
+         v) * ::std::f64::consts::SQRT_2
+}
+
+fn wrap(n: i64, period: i64) -> i64 {
+    ((n % period) + period) % period
+}
+
+fn hash(x: i64, y: i64, seed: u64) -> u64 {
+    let mut h = seed ^ (x as u64).wrapping_mul(0x9E3779B97F4A7C15) ^ (y as u64).wrapping_mul(0xC2B2AE3D27D4EB4F);
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D049BB133111EB);
+    h ^ (h >> 31)
+}
@@ -0,0 +1,191 @@
+use gg::render2::Node;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use std::iter::once;
+use stats::TickCounts;
+use CellGraph;
+
+/// How many samples each plot keeps.
+const SERIES_LEN: usize = 256;
+/// How many ticks pass between samples of the tick-based series.
+const SAMPLE_TICKS: usize = 16;
+
+const ROW_HEIGHT: f32 = 0.07;
+const DIGIT_WIDTH: f32 = 0.02;
+const DIGIT_HEIGHT: f32 = 0.04;
+const DIGIT_SPACING: f32 = 0.03;
+const MAX_DIGITS: usize = 14;
+const HUD_LEFT: f32 = -0.98;
+const HUD_TOP: f32 = 0.97;
+const PLOT_WIDTH: f32 = 0.4;
+const LINE_RADIUS: f32 = 0.003;
+
+const TICK_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const CELL_COLOR: [f32; 4] = [0.0, 1.0, 1.0, 1.0];
+const EDGE_COLOR: [f32; 4] = [0.0, 1.0, 0.0, 1.0];
+const ENERGY_COLOR: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
+const BIRTH_COLOR: [f32; 4] = [1.0, 0.0, 1.0, 1.0];
+const DEATH_COLOR: [f32; 4] = [1.0, 0.2, 0.2, 1.0];
+
+/// Segments of a seven-segment display as a bitmask for each digit (bit 0 is the top segment, then clockwise,
+/// with bit 6 being the middle).
+const DIGIT_SEGMENTS: [u8; 10] = [0x3F, 0x06, 0x5B, 0x4F, 0x66, 0x6D, 0x7D, 0x07, 0x7F, 0x6F];
+/// The endpoints of each segment in a unit digit box with the origin in the bottom left.
+const SEGMENT_LINES: [([f32; 2], [f32; 2]); 7] = [([0.0, 1.0], [1.0, 1.0]),
+                                                  ([1.0, 1.0], [1.0, 0.5]),
+                                                  ([1.0, 0.5], [1.0, 0.0]),
+                                                  ([0.0, 0.0], [1.0, 0.0]),
+                                                  ([0.0, 0.5], [0.0, 0.0]),
+                                                  ([0.0, 1.0], [0.0, 0.5]),
+                                                  ([0.0, 0.5], [1.0, 0.5])];
+
+/// A rolling window of samples.
+struct Series {
+    values: VecDeque<f64>,
+}
+
+impl Series {
+    fn new() -> Series {
+        Series { values: VecDeque::with_capacity(SERIES_LEN) }
+    }
+
+    fn push(&mut self, value: f64) {
+        if self.values.len() == SERIES_LEN {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    /// Adds line segments that plot the series into the box starting at `left` and `bottom`.
+    fn plot(&self, nodes: &mut Vec<Node>, left: f32, bottom: f32, color: [f32; 4]) {
+        let max = self.values.iter().cloned().fold(0.0, f64::max);
+        let min = self.values.iter().cloned().fold(max, f64::min);
+        let range = if max > min { max - min } else { 1.0 };
+        let height = ROW_HEIGHT * 0.8;
+        let points = self.values
+            .iter()
+            .enumerate()
+            .map(|(ix, &v)| {
+                     [left + PLOT_WIDTH * ix as f32 / (SERIES_LEN - 1) as f32,
+                      bottom + height * ((v - min) / range) as f32]
+                 })
+            .collect::<Vec<_>>();
+        for pair in points.windows(2) {
+            push_line(nodes, pair[0], pair[1], color);
+        }
+    }
+}
+
+/// Tracks the series displayed on the heads-up display.
+pub struct Hud {
+    ticks: usize,
+    cells: Series,
+    edges: Series,
+    energy: Series,
+    births: Series,
+    deaths: Series,
+    births_this_second: usize,
+    deaths_this_second: usize,
+    births_per_second: usize,
+    deaths_per_second: usize,
+    last_second: Instant,
+    last_cells: usize,
+    last_edges: usize,
+    last_energy: usize,
+}
+
+impl Hud {
+    pub fn new() -> Hud {
+        Hud {
+            ticks: 0,
+            cells: Series::new(),
+            edges: Series::new(),
+            energy: Series::new(),
+            births: Series::new(),
+            deaths: Series::new(),
+            births_this_second: 0,
+            deaths_this_second: 0,
+            births_per_second: 0,
+            deaths_per_second: 0,
+            last_second: Instant::now(),
+            last_cells: 0,
+            last_edges: 0,
+            last_energy: 0,
+        }
+    }
+
+    /// Records the state of the world after a tick.
+    pub fn update(&mut self, graph: &CellGraph, counts: &TickCounts) {
+        self.ticks += 1;
+        self.last_cells = graph.node_count();
+        self.last_edges = graph.edge_count();
+        self.last_energy = graph.node_indices().map(|nix| graph[nix].cell.energy()).sum();
+        if self.ticks % SAMPLE_TICKS == 0 {
+            self.cells.push(self.last_cells as f64);
+            self.edges.push(self.last_edges as f64);
+            self.energy.push(self.last_energy as f64);
+        }
+
+        self.births_this_second += counts.births;
+        self.deaths_this_second += counts.deaths;
+        if self.last_second.elapsed() >= Duration::from_secs(1) {
+            self.births_per_second = self.births_this_second;
+            self.deaths_per_second = self.deaths_this_second;
+            self.births.push(self.births_per_second as f64);
+            self.deaths.push(self.deaths_per_second as f64);
+            self.births_this_second = 0;
+            self.deaths_this_second = 0;
+            self.last_second = Instant::now();
+        }
+    }
+
+    /// Produces the edges which draw the display in normalized device coordinates.
+    pub fn nodes(&self) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        let rows = [(self.ticks, None, TICK_COLOR),
+                    (self.last_cells, Some(&self.cells), CELL_COLOR),
+                    (self.last_edges, Some(&self.edges), EDGE_COLOR),
+                    (self.last_energy, Some(&self.energy), ENERGY_COLOR),
+                    (self.births_per_second, Some(&self.births), BIRTH_COLOR),
+                    (self.deaths_per_second, Some(&self.deaths), DEATH_COLOR)];
+        for (row, &(value, series, color)) in rows.iter().enumerate() {
+            let bottom = HUD_TOP - ROW_HEIGHT * (row + 1) as f32;
+            push_number(&mut nodes, value, HUD_LEFT, bottom, color);
+            if let Some(series) = series {
+                series.plot(&mut nodes,
+                            HUD_LEFT + DIGIT_SPACING * MAX_DIGITS as f32,
+                            bottom,
+                            color);
+            }
+        }
+        nodes
+    }
+}
+
+fn push_line(nodes: &mut Vec<Node>, p0: [f32; 2], p1: [f32; 2], color: [f32; 4]) {
+    let node = |position| {
+        Node {
+            position: position,
+            inner_color: color,
+            falloff: 0.5,
+            falloff_color: color,
+            falloff_radius: LINE_RADIUS,
+            inner_radius: 0.0,
+        }
+    };
+    nodes.extend(once(node(p0)).chain(once(node(p1))));
+}
+
+fn push_number(nodes: &mut Vec<Node>, value: usize, left: f32, bottom: f32, color: [f32; 4]) {
+    for (ix, digit) in value.to_string().bytes().map(|b| (b - b'0') as usize).enumerate().take(MAX_DIGITS) {
+        let x = left + DIGIT_SPACING * ix as f32;
+        for (segment, &(p0, p1)) in SEGMENT_LINES.iter().enumerate() {
+            if DIGIT_SEGMENTS[digit] & (1 << segment) != 0 {
+                push_line(nodes,
+                          [x + p0[0] * DIGIT_WIDTH, bottom + p0[1] * DIGIT_HEIGHT],
+                          [x + p1[0] * DIGIT_WIDTH, bottom + p1[1] * DIGIT_HEIGHT],
+                          color);
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashSet;
+use petgraph::stable_graph::NodeIndex;
+use petgraph::visit::{EdgeRef, IntoEdgeReferences};
+use nalgebra::Norm;
+use rand::Rng;
+use zoom::Toroid;
+use auxillary::{area_box, compute_newton_coefficient, map_node_to_mag};
+use CellGraph;
+
+/// The number of bins relatedness from 0 to 1 is split into.
+pub const RELATEDNESS_BINS: usize = 10;
+/// The most pairs sampled for each histogram.
+const KIN_SAMPLES: usize = 256;
+
+/// Histograms of the relatedness of cells which are connected and cells which are not.
+#[derive(Clone, Debug, Default)]
+pub struct KinHistograms {
+    pub connected: [usize; RELATEDNESS_BINS],
+    pub unconnected: [usize; RELATEDNESS_BINS],
+    pub connected_mean: f64,
+    pub unconnected_mean: f64,
+}
+
+impl KinHistograms {
+    /// Measures the relatedness of the endpoints of edges and of random unconnected pairs, sampling when there
+    /// are many.
+    pub fn measure<R: Rng>(graph: &CellGraph, rng: &mut R) -> KinHistograms {
+        let mut histograms = KinHistograms::default();
+
+        let edges = graph.edge_references().map(|er| (er.source(), er.target())).collect::<Vec<_>>();
+        let connected = relatedness(graph, &sample(&edges, rng));
+        histograms.connected = histogram(&connected);
+        histograms.connected_mean = mean(&connected);
+
+        // Sample unconnected pairs by drawing random pairs and rejecting connected ones.
+        let nodes = graph.node_indices().collect::<Vec<_>>();
+        let linked = edges.iter()
+            .flat_map(|&(a, b)| Some((a, b)).into_iter().chain(Some((b, a))))
+            .collect::<HashSet<_>>();
+        let mut unconnected = Vec::new();
+        if nodes.len() >= 2 {
+            for _ in 0..KIN_SAMPLES * 2 {
+                let i = rng.gen_range(0, nodes.len());
+                let j = (i + rng.gen_range(1, nodes.len())) % nodes.len();
+                if !linked.contains(&(nodes[i], nodes[j])) {
+                    unconnected.push((nodes[i], nodes[j]));
+                    if unconnected.len() == KIN_SAMPLES {
+                        break;
+                    }
+                }
+            }
+        }
+        let unconnected = relatedness(graph, &unconnected);
+        histograms.unconnected = histogram(&unconnected);
+        histograms.unconnected_mean = mean(&unconnected);
+        histograms
+    }
+}
+
+/// Computes the mean relatedness between the endpoints of the edges leaving the given cells, sampling when there
+/// are many. Gives `None` if there are no such edges.
+pub fn mean_edge_relatedness<R: Rng>(graph: &CellGraph, nodes: &[NodeIndex<u32>], rng: &mut R) -> Option<f64> {
+    let edges = nodes.iter()
+        .flat_map(|&nix| graph.edges(nix).map(|er| (er.source(), er.target())))
+        .collect::<Vec<_>>();
+    if edges.is_empty() {
+        return None;
+    }
+    Some(mean(&relatedness(graph, &sample(&edges, rng))))
+}
+
+/// Computes the fraction of the repulsive push exerted by the given cells which lands on each other rather
+/// than on cells outside the group.
+///
+/// Each cell is credited with its own share of every pair's repulsion coefficient, divided by the squared
+/// distance. Pushing organism-mates apart earns them food, so a high fraction means repulsion mostly benefits
+/// neighbors, while a low fraction means it is mostly spent clearing space around the group.
+pub fn neighbor_repulsion_fraction(graph: &CellGraph, nodes: &[NodeIndex<u32>]) -> f64 {
+    let members = nodes.iter().cloned().collect::<HashSet<_>>();
+    let (mut neighbors, mut total) = (0.0, 0.0);
+    for &nix in nodes {
+        let own = map_node_to_mag(&graph[nix]);
+        for other in graph.node_indices().filter(|&other| other != nix) {
+            let distance_squared = area_box()
+                .wrap_delta(graph[other].cell.position() - graph[nix].cell.position())
+                .norm_squared()
+                .max(1.0);
+            let theirs = map_node_to_mag(&graph[other]);
+            // This cell's share of the pair's coefficient.
+            let share = own / (own + theirs).max(::std::f64::EPSILON);
+            let push = compute_newton_coefficient((own, theirs)) * share / distance_squared;
+            total += push;
+            if members.contains(&other) {
+                neighbors += push;
+            }
+        }
+    }
+    if total > 0.0 { neighbors / total } else { 0.0 }
+}
+
+fn sample<T: Copy, R: Rng>(items: &[T], rng: &mut R) -> Vec<T> {
+    if items.len() <= KIN_SAMPLES {
+        items.to_vec()
+    } else {
+        (0..KIN_SAMPLES).map(|_| items[rng.gen_range(0, items.len())]).collect()
+    }
+}
+
+fn relatedness(graph: &CellGraph, pairs: &[(NodeIndex<u32>, NodeIndex<u32>)]) -> Vec<f64> {
+    pairs.iter().map(|&(a, b)| graph[a].cell.relatedness(&graph[b].cell)).collect()
+}
+
+fn histogram(relatedness: &[f64]) -> [usize; RELATEDNESS_BINS] {
+    let mut bins = [0; RELATEDNESS_BINS];
+    for &r in relatedness {
+        bins[((r * RELATEDNESS_BINS as f64) as usize).min(RELATEDNESS_BINS - 1)] += 1;
+    }
+    bins
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() { 0.0 } else { values.iter().sum::<f64>() / values.len() as f64 }
+}
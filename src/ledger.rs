@@ -0,0 +1,93 @@
+use stats::Row;
+use CellGraph;
+
+/// Where energy came from and went to over a tick or a span of ticks.
+#[derive(Clone, Debug, Default)]
+pub struct EnergyLedger {
+    /// Gained by cells from food.
+    pub food: usize,
+    /// Brought in by spawned cells.
+    pub spawned: usize,
+    /// Spent on the static cost every cell pays each tick.
+    pub basal: usize,
+    /// Spent on the cost of the size of cells' brains.
+    pub size: usize,
+    /// Spent on executing cells' brains.
+    pub execution: usize,
+    /// Spent on emitting chemicals.
+    pub emission: usize,
+    /// Held by cells when they died.
+    pub died: usize,
+    /// Handed from dividing cells to their children; this moves energy between cells without changing the total.
+    pub divided: usize,
+}
+
+impl EnergyLedger {
+    pub fn accumulate(&mut self, other: &EnergyLedger) {
+        self.food += other.food;
+        self.spawned += other.spawned;
+        self.basal += other.basal;
+        self.size += other.size;
+        self.execution += other.execution;
+        self.emission += other.emission;
+        self.died += other.died;
+        self.divided += other.divided;
+    }
+
+    pub fn income(&self) -> usize {
+        self.food + self.spawned
+    }
+
+    pub fn expenses(&self) -> usize {
+        self.basal + self.size + self.execution + self.emission + self.died
+    }
+
+    /// Panics if the change in the total energy of the cells isn't exactly what the ledger accounts for.
+    pub fn check_balance(&self, before: usize, after: usize) {
+        assert_eq!(before + self.income(),
+                   after + self.expenses(),
+                   "energy isn't conserved: {:?}",
+                   self);
+    }
+
+    pub fn push_columns(&self, row: &mut Row) {
+        row.push("energy_food", self.food as f64);
+        row.push("energy_spawned", self.spawned as f64);
+        row.push("energy_basal", self.basal as f64);
+        row.push("energy_size", self.size as f64);
+        row.push("energy_execution", self.execution as f64);
+        row.push("energy_emission", self.emission as f64);
+        row.push("energy_died", self.died as f64);
+        row.push("energy_divided", self.divided as f64);
+    }
+}
+
+pub fn total_energy(graph: &CellGraph) -> usize {
+    graph.node_indices().map(|nix| graph[nix].cell.energy()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EnergyLedger;
+
+    #[test]
+    fn division_only_tick_balances() {
+        // A cell with 1000 energy hands 400 to its child, spending 10 on deciding how to divide.
+        let ledger = EnergyLedger {
+            execution: 10,
+            divided: 400,
+            ..Default::default()
+        };
+        ledger.check_balance(1000, 990);
+    }
+
+    #[test]
+    #[should_panic]
+    fn division_counted_as_loss_panics() {
+        let ledger = EnergyLedger {
+            divided: 400,
+            ..Default::default()
+        };
+        ledger.check_balance(1000, 600);
+    }
+}
@@ -0,0 +1,89 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use rand::Rng;
+use serde_json;
+use cell::Genome;
+use CellGraph;
+use petgraph::stable_graph::NodeIndex;
+
+const GENOME_EXTENSION: &'static str = "json";
+
+/// A collection of genomes saved from previous runs which can be used to seed a world.
+#[derive(Clone, Debug, Default)]
+pub struct GenomeLibrary {
+    genomes: Vec<Genome>,
+}
+
+impl GenomeLibrary {
+    /// Loads every genome in a directory. A directory that doesn't exist is an empty library, and files which
+    /// can't be read as a genome are skipped with a warning.
+    pub fn load<P: AsRef<Path>>(directory: P) -> io::Result<GenomeLibrary> {
+        let directory = directory.as_ref();
+        if !directory.is_dir() {
+            return Ok(GenomeLibrary::default());
+        }
+        let mut paths = fs::read_dir(directory)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        // Sort so the same library always seeds the same world.
+        paths.sort();
+        let genomes = paths.into_iter()
+            .filter(|path| path.extension().map(|e| e == GENOME_EXTENSION).unwrap_or(false))
+            .filter_map(|path| match load_genome(&path) {
+                            Ok(genome) => Some(genome),
+                            Err(e) => {
+                                eprintln!("skipping genome {}: {}", path.display(), e);
+                                None
+                            }
+                        })
+            .collect();
+        Ok(GenomeLibrary { genomes: genomes })
+    }
+
+    /// Picks a random genome from the library if it isn't empty.
+    pub fn choose<R: Rng>(&self, rng: &mut R) -> Option<Genome> {
+        rng.choose(&self.genomes).cloned()
+    }
+}
+
+pub fn load_genome<P: AsRef<Path>>(path: P) -> io::Result<Genome> {
+    let file = BufReader::new(File::open(path)?);
+    serde_json::from_reader(file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_genome(file: File, genome: &Genome) -> io::Result<()> {
+    let mut file = BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut file, genome).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+}
+
+/// Saves a genome under the first free name with the given stem so genomes from earlier runs, whose cell ids
+/// start over at 0, are never overwritten.
+fn save_genome(directory: &Path, stem: &str, genome: &Genome) -> io::Result<PathBuf> {
+    for n in 0.. {
+        let path = match n {
+            0 => directory.join(format!("{}.{}", stem, GENOME_EXTENSION)),
+            n => directory.join(format!("{}_{}.{}", stem, n, GENOME_EXTENSION)),
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return write_genome(file, genome).map(|_| path),
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!()
+}
+
+/// Saves the genomes of the given cells into the library directory, named by cell id, and returns the paths.
+pub fn export_genomes<P: AsRef<Path>>(directory: P,
+                                      graph: &CellGraph,
+                                      nodes: &[NodeIndex<u32>])
+                                      -> io::Result<Vec<PathBuf>> {
+    let directory = directory.as_ref();
+    fs::create_dir_all(directory)?;
+    nodes.iter()
+        .map(|&nix| {
+                 save_genome(directory, &format!("genome_{}", graph[nix].id), graph[nix].cell.genome())
+             })
+        .collect()
+}
@@ -15,10 +15,28 @@ extern crate petgraph;
 extern crate num;
 extern crate itertools;
 extern crate boolinator;
+extern crate png;
+extern crate serde_json;
 
 mod circle;
 mod cell;
 mod auxillary;
+mod hud;
+mod raster;
+mod color;
+mod svg;
+mod stats;
+mod organism;
+mod library;
+mod spawn;
+mod diversity;
+mod species;
+mod kin;
+mod chemical;
+mod physics;
+mod flow;
+mod obstacle;
+mod ledger;
 
 use auxillary::*;
 use gg::render2::*;
@@ -28,8 +46,8 @@ use petgraph::visit::{EdgeRef, IntoEdgeReferences};
 use nalgebra::Norm;
 
 /// Create the graph which is used to store the cells and all their connections.
-/// The cell it goes out from is the first weight and vice versa.
-type CellGraph = petgraph::stable_graph::StableGraph<CellContainer, (cell::ConnectionDelta, cell::ConnectionDelta)>;
+/// Each edge goes out from the cell whose delta is its `source` and into the cell whose delta is its `target`.
+type CellGraph = petgraph::stable_graph::StableGraph<CellContainer, cell::Connection>;
 
 const SIZE_SCALE: f64 = 0.6;
 
@@ -38,10 +56,136 @@ const CIRCLE_SCALE: f32 = 0.015 / SIZE_SCALE as f32;
 const DYNAMIC_ENERGY_GAIN_COEFFICIENT: f64 = 1.0;
 const RENDER_LENGTH_LIMIT: f64 = 1000.0;
 
-const CENTER_BAND_RATIO: f64 = 0.5;
-const CENTER_BAND_ACCELERATION: f64 = 100.0;
+/// The currents cells are carried by, added together.
+const FLOWS: &'static [flow::Flow] = &[flow::Flow::ShearBand {
+                                           ratio: 0.5,
+                                           acceleration: 100.0,
+                                       }];
+
+/// How cell motion is integrated and how many physics steps each brain tick is split into.
+const INTEGRATOR: physics::Integrator = physics::Integrator::SemiImplicitEuler;
+const PHYSICS_SUBSTEPS: usize = 1;
+/// Whether cells collide as hard bodies with a radius that grows with their energy.
+/// Renderers draw cells at their real radius when this is set.
+const COLLISIONS: bool = false;
+/// The file obstacles are loaded from; without it the world is open.
+const SCENARIO_PATH: &'static str = "scenario.json";
+/// Whether to check every tick that the energy ledger accounts for every change in the cells' total energy.
+const CHECK_ENERGY_BALANCE: bool = cfg!(debug_assertions);
+
+/// How many ticks pass between frames written in headless mode.
+const FRAME_TICKS: usize = 16;
+const FRAME_WIDTH: u32 = 640;
+const FRAME_HEIGHT: u32 = 640;
+const FRAME_DIRECTORY: &'static str = "frames";
+/// How many ticks pass between SVG snapshots written in headless mode.
+const SNAPSHOT_TICKS: usize = 4096;
+
+const COLOR_MODE: color::ColorMode = color::ColorMode::Uniform;
+
+/// How many ticks pass between rows of the statistics log.
+const STATS_TICKS: usize = 64;
+const STATS_FORMAT: stats::StatsFormat = stats::StatsFormat::Csv;
+const STATS_PATH: &'static str = "stats.csv";
+/// How many ticks pass between finding organisms and writing their reports.
+const ORGANISM_TICKS: usize = 256;
+const ORGANISMS_PATH: &'static str = "organisms.csv";
+/// The directory genomes are exported into and seeded from.
+const LIBRARY_DIRECTORY: &'static str = "library";
+/// How many ticks pass between clustering cells into species.
+const SPECIES_TICKS: usize = 256;
+const SPECIES_PATH: &'static str = "species.csv";
+
+/// Spawns a cell with a random genome at random with a small probability every tick. Random genomes keep runs
+/// reproducible whatever is in the library.
+const SPAWN_POLICY: spawn::SpawnPolicy = spawn::SpawnPolicy {
+    rate: spawn::SpawnRate::Fixed(0.1 * SIZE_SCALE * SIZE_SCALE),
+    location: spawn::SpawnLocation::Anywhere,
+    genomes: spawn::SpawnGenomes::Random,
+};
+/// Whether headless runs periodically export a genome from the largest organism into the library.
+const LIBRARY_EXPORT: bool = false;
+/// How many ticks pass between exporting a genome from the largest organism in headless mode.
+const LIBRARY_EXPORT_TICKS: usize = ORGANISM_TICKS * 16;
+
+const HEADLESS_ARG: &'static str = "--headless";
+const DISASSEMBLE_COMMAND: &'static str = "disassemble";
+const ELIMINATE_DEAD_CODE_ARG: &'static str = "--eliminate-dead-code";
 
 fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.first().map(|arg| arg == DISASSEMBLE_COMMAND).unwrap_or(false) {
+        disassemble(&args[1..]);
+    } else if args.iter().any(|arg| arg == HEADLESS_ARG) {
+        run_headless();
+    } else {
+        run_windowed();
+    }
+}
+
+/// Prints the instruction listing of a genome stored as JSON.
+fn disassemble(args: &[String]) {
+    let eliminate_dead_code = args.iter().any(|arg| arg == ELIMINATE_DEAD_CODE_ARG);
+    let path = match args.iter().find(|arg| !arg.starts_with("--")) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: evomush {} <genome.json> [{}]",
+                      DISASSEMBLE_COMMAND,
+                      ELIMINATE_DEAD_CODE_ARG);
+            std::process::exit(1);
+        }
+    };
+    let genome = library::load_genome(path).unwrap_or_else(|e| {
+        eprintln!("unable to read genome from {}: {}", path, e);
+        std::process::exit(1);
+    });
+    print!("{}", genome.disassemble(eliminate_dead_code));
+}
+
+/// Runs the simulation without a display, periodically writing frames with the software renderer.
+fn run_headless() {
+    use rand::SeedableRng;
+    let mut graph = CellGraph::new();
+    let mut rng = rand::Isaac64Rng::from_seed(&SEED);
+    let mut frames = raster::FrameWriter::new(FRAME_DIRECTORY, FRAME_WIDTH, FRAME_HEIGHT);
+    let mut stats = stats::StatsLogger::create(STATS_PATH, STATS_FORMAT, STATS_TICKS).unwrap();
+    let mut organisms = Organisms::create().unwrap();
+    let mut species = species::SpeciesTracker::new(SPECIES_TICKS);
+    let mut species_rows = stats::RowWriter::create(SPECIES_PATH, STATS_FORMAT).unwrap();
+    let mut environment = Environment::new();
+
+    for tick in 0.. {
+        let counts = step(&mut graph, &mut environment, &mut rng);
+        stats.record(&graph, &counts).unwrap();
+        if let Some(rows) = species.update(&mut graph) {
+            for row in rows {
+                species_rows.write(&row).unwrap();
+            }
+            species_rows.flush().unwrap();
+        }
+        let organisms_updated = organisms.update(&graph).unwrap();
+        if tick % FRAME_TICKS == 0 {
+            let palette = color::Palette::new(COLOR_MODE, &graph);
+            frames.write_frame(&circle_qbeziers(&graph, &palette), &edge_nodes(&graph)).unwrap();
+        }
+        if tick % SNAPSHOT_TICKS == 0 {
+            let palette = color::Palette::new(COLOR_MODE, &graph);
+            svg::save_svg(&graph, &palette, format!("snapshot_{:08}.svg", tick)).unwrap();
+        }
+        // Keep a genome from the most successful organism every so often. Its members are mostly one lineage,
+        // so only the one with the most energy represents it.
+        if LIBRARY_EXPORT && organisms_updated && organisms.tracker.tick() % LIBRARY_EXPORT_TICKS == 0 {
+            if let Some(&nix) = organisms.largest().and_then(|nodes| {
+                                    nodes.iter().max_by_key(|&&nix| graph[nix].cell.energy())
+                                }) {
+                library::export_genomes(LIBRARY_DIRECTORY, &graph, &[nix]).unwrap();
+            }
+        }
+    }
+}
+
+/// Runs the simulation with a live view.
+fn run_windowed() {
     use glium_sdl2::DisplayBuild;
     use rand::SeedableRng;
     let sdl_context = sdl2::init().unwrap();
@@ -60,77 +204,28 @@ fn main() {
 
     let mut graph = CellGraph::new();
     let mut rng = rand::Isaac64Rng::from_seed(&SEED);
+    let mut hud = hud::Hud::new();
+    let mut stats = stats::StatsLogger::create(STATS_PATH, STATS_FORMAT, STATS_TICKS).unwrap();
+    let mut organisms = Organisms::create().unwrap();
+    let mut species = species::SpeciesTracker::new(SPECIES_TICKS);
+    let mut species_rows = stats::RowWriter::create(SPECIES_PATH, STATS_FORMAT).unwrap();
+    let mut environment = Environment::new();
+    let mut snapshots = 0..;
 
     loop {
         use glium::Surface;
 
-        // Generate cells randomly.
-        generate_cells(&mut graph, &mut rng);
-
-        // Compute cell deltas.
-        for nix in graph.node_indices().collect::<Vec<_>>() {
-            use petgraph::Direction::*;
-
-            let out_states = compute_connection_states(&mut graph, nix, Outgoing);
-            let in_states = compute_connection_states(&mut graph, nix, Incoming);
-
-            let cc = graph.node_weight_mut(nix).unwrap();
-            let state = cc.cell.create_state(out_states, in_states);
-            cc.delta = Some(cc.cell.cycle(state));
-        }
-
-        // Update all edge deltas.
-        for nix in graph.node_indices().collect::<Vec<_>>() {
-            use petgraph::Direction::*;
-
-            // Handle the connection deltas.
-            update_deltas(&mut graph, nix, Outgoing);
-            update_deltas(&mut graph, nix, Incoming);
-        }
-
-        // Handle cell physics interations.
-        cell_physics_interactions(&mut graph);
-
-        // Advance physics
-        for nix in graph.node_indices().collect::<Vec<_>>() {
-            let y = graph[nix].cell.position().y;
-            if y.abs() < area_box().offset.y * CENTER_BAND_RATIO {
-                graph[nix].cell.impulse(nalgebra::Vector2::new(
-                    y / (area_box().offset.y * CENTER_BAND_RATIO) * CENTER_BAND_ACCELERATION, 0.0));
-            }
-            graph[nix].cell.update_physics();
-        }
-
-        // Handle division
-        for nix in graph.node_indices().collect::<Vec<_>>() {
-            if graph[nix].delta.as_ref().map(|d| d.divide).unwrap_or(false) {
-                divide_cell(&mut graph, nix, &mut rng);
+        let counts = step(&mut graph, &mut environment, &mut rng);
+        hud.update(&graph, &counts);
+        stats.record(&graph, &counts).unwrap();
+        if let Some(rows) = species.update(&mut graph) {
+            for row in rows {
+                species_rows.write(&row).unwrap();
             }
+            species_rows.flush().unwrap();
         }
-
-        // Handle death
-        for nix in graph.node_indices().collect::<Vec<_>>() {
-            if graph[nix].delta.as_ref().map(|d| d.die).unwrap_or(false) {
-                graph.remove_node(nix);
-            }
-        }
-
-        for eix in graph.edge_references().filter_map(|er| {
-            (er.weight().0.sever || er.weight().0.sever).as_some(er.id())
-        }).collect::<Vec<_>>() {
-            graph.remove_edge(eix);
-        }
-
-        // Give everybody food based on closest distance squared.
-        for nix in graph.node_indices().collect::<Vec<_>>() {
-            let add_energy = graph[nix]
-                    .cell
-                    .closest_distance_squared()
-                    .map(|d| (d * DYNAMIC_ENERGY_GAIN_COEFFICIENT) as usize)
-                    .unwrap_or(0);
-            let new_energy = graph[nix].cell.energy() + add_energy;
-            graph[nix].cell.set_energy(new_energy);
-        }
+        organisms.update(&graph).unwrap();
+        let palette = color::Palette::new(COLOR_MODE, &graph);
 
         // Get dimensions each frame.
         let dims = display.get_framebuffer_dimensions();
@@ -148,27 +243,7 @@ fn main() {
                                    [[hscale / area_box().offset.x as f32, 0.0, 0.0],
                                     [0.0, 1.0 / area_box().offset.y as f32, 0.0],
                                         [0.0, 0.0, 1.0]],
-                                   &graph.node_indices()
-                                         .map(|nix| graph[nix].cell.position())
-                                         .flat_map(|p| circle::make_circle([0.0, 1.0, 1.0, 1.0]).map(move |mut qb| {
-                                             qb.falloff_radius0 *= CIRCLE_SCALE * area_box().offset.y as f32;
-                                             qb.falloff_radius1 *= CIRCLE_SCALE * area_box().offset.y as f32;
-
-                                             qb.position0[0] *= CIRCLE_SCALE * area_box().offset.x as f32;
-                                             qb.position0[0] += p.x as f32;
-                                             qb.position0[1] *= CIRCLE_SCALE * area_box().offset.y as f32;
-                                             qb.position0[1] += p.y as f32;
-                                             qb.position1[0] *= CIRCLE_SCALE * area_box().offset.x as f32;
-                                             qb.position1[0] += p.x as f32;
-                                             qb.position1[1] *= CIRCLE_SCALE * area_box().offset.y as f32;
-                                             qb.position1[1] += p.y as f32;
-                                             qb.position2[0] *= CIRCLE_SCALE * area_box().offset.x as f32;
-                                             qb.position2[0] += p.x as f32;
-                                             qb.position2[1] *= CIRCLE_SCALE * area_box().offset.y as f32;
-                                             qb.position2[1] += p.y as f32;
-                                             qb
-                                         }))
-                                         .collect::<Vec<_>>());
+                                   &circle_qbeziers(&graph, &palette));
         // Draw edges.
         glowy.render_edges_round(&mut target,
                                    [[1.0, 0.0, 0.0],
@@ -177,23 +252,12 @@ fn main() {
                                    [[hscale / area_box().offset.x as f32, 0.0, 0.0],
                                     [0.0, 1.0 / area_box().offset.y as f32, 0.0],
                                         [0.0, 0.0, 1.0]],
-                                   &graph.edge_references()
-                                         .map(|er| (graph[er.source()].cell.position(), graph[er.target()].cell.position()))
-                                         .filter(|&(p0, p1)| (p0 - p1).norm_squared() < RENDER_LENGTH_LIMIT.powi(2))
-                                         .flat_map(|(p0, p1)| once(Node{position: [p0.x as f32, p0.y as f32],
-                                            inner_color: [0.0, 0.0, 0.0, 1.0],
-                                            falloff: 0.2,
-                                            falloff_color: [0.0, 0.35, 0.0, 1.0],
-                                            falloff_radius: CIRCLE_SCALE * area_box().offset.y as f32,
-                                            inner_radius: 0.0}).chain(once(
-                                                Node{position: [p1.x as f32, p1.y as f32],
-                                            inner_color: [0.0, 0.0, 0.0, 1.0],
-                                            falloff: 0.2,
-                                            falloff_color: [0.0, 0.35, 0.0, 1.0],
-                                            falloff_radius: CIRCLE_SCALE * area_box().offset.y as f32,
-                                            inner_radius: 0.0}
-                                            )))
-                                         .collect::<Vec<_>>());
+                                   &edge_nodes(&graph));
+        // Draw the heads-up display.
+        glowy.render_edges_round(&mut target,
+                                 [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+                                 [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+                                 &hud.nodes());
 
         // End draw.
         target.finish().unwrap();
@@ -201,19 +265,344 @@ fn main() {
         // Handle events.
         for event in event_pump.poll_iter() {
             use sdl2::event::Event;
+            use sdl2::keyboard::Keycode;
 
             match event {
                 Event::Quit { .. } => {
                     return;
                 }
+                Event::KeyDown { keycode: Some(Keycode::S), .. } => {
+                    let path = format!("snapshot_{:04}.svg", snapshots.next().unwrap());
+                    svg::save_svg(&graph, &palette, path).unwrap();
+                }
+                Event::MouseButtonDown { x, y, .. } => {
+                    // Export the genome of the cell closest to where the mouse was clicked.
+                    let point = nalgebra::Vector2::new(
+                        (2.0 * x as f64 / dims.0 as f64 - 1.0) * area_box().offset.x / hscale as f64,
+                        (1.0 - 2.0 * y as f64 / dims.1 as f64) * area_box().offset.y);
+                    if let Some(nix) = closest_cell(&graph, point) {
+                        library::export_genomes(LIBRARY_DIRECTORY, &graph, &[nix]).unwrap();
+                    }
+                }
                 _ => (),
             }
         }
     }
 }
 
+/// Advances the simulation by one tick and returns the births and deaths that occurred.
+fn step<R: rand::Rng>(graph: &mut CellGraph,
+                      environment: &mut Environment,
+                      rng: &mut R)
+                      -> stats::TickCounts {
+    step_with(graph, environment, rng, |_| {})
+}
+
+/// Runs a tick like `step`, letting `adjust` change the cells' deltas before they are acted on.
+fn step_with<R, F>(graph: &mut CellGraph,
+                   environment: &mut Environment,
+                   rng: &mut R,
+                   adjust: F)
+                   -> stats::TickCounts
+    where R: rand::Rng,
+          F: FnOnce(&mut CellGraph)
+{
+    let mut counts = stats::TickCounts::default();
+    let initial_energy = ledger::total_energy(graph);
+
+    // Spawn new cells.
+    let spawned = environment.spawner.spawn(graph, rng);
+    counts.births += spawned;
+    counts.spawns += spawned;
+    counts.energy.spawned = ledger::total_energy(graph) - initial_energy;
+
+    // Compute cell deltas.
+    for nix in graph.node_indices().collect::<Vec<_>>() {
+        use petgraph::Direction::*;
+
+        let out_states = compute_connection_states(graph, nix, Outgoing);
+        let in_states = compute_connection_states(graph, nix, Incoming);
+
+        let cc = graph.node_weight_mut(nix).unwrap();
+        let chemicals = environment.chemicals.sample(cc.cell.position());
+        let state = cc.cell.create_state(chemicals, out_states, in_states);
+        let delta = cc.cell.cycle(state);
+        counts.energy.accumulate(&delta.spent);
+        for (total, &cycles) in counts.cycles.iter_mut().zip(delta.cycles.iter()) {
+            *total += cycles;
+        }
+        cc.delta = Some(delta);
+    }
+    adjust(graph);
+
+    // Emit chemicals only once every cell has sampled them so no cell senses another's emissions early.
+    for nix in graph.node_indices() {
+        if let Some(delta) = graph[nix].delta.as_ref() {
+            environment.chemicals.emit(graph[nix].cell.position(), &delta.emissions);
+        }
+    }
+
+    // Diffuse and decay the chemicals.
+    environment.chemicals.advance();
+
+    // Update all edge deltas.
+    for nix in graph.node_indices().collect::<Vec<_>>() {
+        use petgraph::Direction::*;
+
+        // Handle the connection deltas.
+        update_deltas(graph, nix, Outgoing);
+        update_deltas(graph, nix, Incoming);
+    }
+
+    // Measure the distances cells are fed by once, before physics, so food doesn't depend on the integrator.
+    record_closest_distances(graph);
+
+    // Handle cell physics interations and advance physics.
+    physics::advance(graph,
+                     INTEGRATOR,
+                     PHYSICS_SUBSTEPS,
+                     COLLISIONS,
+                     &environment.scenario,
+                     |graph| {
+        cell_physics_interactions(graph);
+        environment.flow.apply(graph);
+    });
+    environment.flow.advance();
+
+    // Handle division
+    for nix in graph.node_indices().collect::<Vec<_>>() {
+        if graph[nix].delta.as_ref().map(|d| d.divide).unwrap_or(false) {
+            let before = graph[nix].cell.energy();
+            let child = divide_cell(graph, nix, rng);
+            let (parent_energy, child_energy) = (graph[nix].cell.energy(), graph[child].cell.energy());
+            // Deciding how connections are passed on is paid for by executing the brain.
+            counts.energy.execution += before - parent_energy - child_energy;
+            counts.energy.divided += child_energy;
+            counts.births += 1;
+            counts.divisions += 1;
+        }
+    }
+
+    // Handle death
+    for nix in graph.node_indices().collect::<Vec<_>>() {
+        if graph[nix].delta.as_ref().map(|d| d.die).unwrap_or(false) {
+            counts.energy.died += graph[nix].cell.energy();
+            graph.remove_node(nix);
+            counts.deaths += 1;
+        }
+    }
+
+    // Connections are also severed when an obstacle comes between the cells.
+    for eix in graph.edge_references().filter_map(|er| {
+        (er.weight().source.sever || er.weight().target.sever ||
+         environment.scenario.blocks(graph[er.source()].cell.position(), graph[er.target()].cell.position()))
+            .as_some(er.id())
+    }).collect::<Vec<_>>() {
+        graph.remove_edge(eix);
+        counts.severs += 1;
+    }
+
+    // Give everybody food based on closest distance squared.
+    for nix in graph.node_indices().collect::<Vec<_>>() {
+        let add_energy = graph[nix]
+                .cell
+                .closest_distance_squared()
+                .map(|d| (d * DYNAMIC_ENERGY_GAIN_COEFFICIENT) as usize)
+                .unwrap_or(0);
+        let new_energy = graph[nix].cell.energy() + add_energy;
+        graph[nix].cell.set_energy(new_energy);
+        counts.energy.food += add_energy;
+    }
+
+    if CHECK_ENERGY_BALANCE {
+        counts.energy.check_balance(initial_energy, ledger::total_energy(graph));
+    }
+
+    counts
+}
+
+/// Creates the curves which draw every cell in world coordinates.
+fn circle_qbeziers(graph: &CellGraph, palette: &color::Palette) -> Vec<QBezier> {
+    graph.node_indices()
+         .map(|nix| {
+             (graph[nix].cell.position(),
+              drawn_radius(&graph[nix].cell) as f32,
+              palette.cell_color(&graph[nix]))
+         })
+         .flat_map(|(p, radius, color)| circle::make_circle(color).map(move |mut qb| {
+             qb.falloff_radius0 *= radius;
+             qb.falloff_radius1 *= radius;
+
+             qb.position0[0] *= radius;
+             qb.position0[0] += p.x as f32;
+             qb.position0[1] *= radius;
+             qb.position0[1] += p.y as f32;
+             qb.position1[0] *= radius;
+             qb.position1[0] += p.x as f32;
+             qb.position1[1] *= radius;
+             qb.position1[1] += p.y as f32;
+             qb.position2[0] *= radius;
+             qb.position2[0] += p.x as f32;
+             qb.position2[1] *= radius;
+             qb.position2[1] += p.y as f32;
+             qb
+         }))
+         .collect()
+}
+
+/// The radius a cell is drawn at in world coordinates.
+fn drawn_radius(cell: &cell::Cell) -> f64 {
+    if COLLISIONS {
+        cell.radius()
+    } else {
+        CIRCLE_SCALE as f64 * area_box().offset.y
+    }
+}
+
+/// Creates the pairs of nodes which draw every edge in world coordinates.
+fn edge_nodes(graph: &CellGraph) -> Vec<Node> {
+    graph.edge_references()
+         .map(|er| (graph[er.source()].cell.position(), graph[er.target()].cell.position()))
+         .filter(|&(p0, p1)| (p0 - p1).norm_squared() < RENDER_LENGTH_LIMIT.powi(2))
+         .flat_map(|(p0, p1)| once(Node{position: [p0.x as f32, p0.y as f32],
+            inner_color: [0.0, 0.0, 0.0, 1.0],
+            falloff: 0.2,
+            falloff_color: color::EDGE_COLOR,
+            falloff_radius: CIRCLE_SCALE * area_box().offset.y as f32,
+            inner_radius: 0.0}).chain(once(
+                Node{position: [p1.x as f32, p1.y as f32],
+            inner_color: [0.0, 0.0, 0.0, 1.0],
+            falloff: 0.2,
+            falloff_color: color::EDGE_COLOR,
+            falloff_radius: CIRCLE_SCALE * area_box().offset.y as f32,
+            inner_radius: 0.0}
+            )))
+         .collect()
+}
+
+/// Everything in the world besides the cells themselves.
+struct Environment {
+    spawner: spawn::Spawner,
+    chemicals: chemical::ChemicalField,
+    flow: flow::FlowField,
+    scenario: obstacle::Scenario,
+}
+
+impl Environment {
+    fn new() -> Environment {
+        let scenario = obstacle::Scenario::load(SCENARIO_PATH).unwrap_or_else(|e| {
+            eprintln!("unable to read scenario {}: {}", SCENARIO_PATH, e);
+            std::process::exit(1);
+        });
+        Environment {
+            spawner: spawn::Spawner::new(SPAWN_POLICY,
+                                         library::GenomeLibrary::load(LIBRARY_DIRECTORY).unwrap_or_else(|e| {
+                                             eprintln!("unable to read genome library {}: {}", LIBRARY_DIRECTORY, e);
+                                             Default::default()
+                                         })),
+            chemicals: chemical::ChemicalField::new(&scenario),
+            flow: flow::FlowField::new(FLOWS),
+            scenario: scenario,
+        }
+    }
+}
+
+/// Tracks organisms and writes out their reports.
+struct Organisms {
+    tracker: organism::OrganismTracker,
+    rows: stats::RowWriter<std::io::BufWriter<std::fs::File>>,
+}
+
+impl Organisms {
+    fn create() -> std::io::Result<Organisms> {
+        Ok(Organisms {
+               tracker: organism::OrganismTracker::new(ORGANISM_TICKS),
+               rows: stats::RowWriter::create(ORGANISMS_PATH, STATS_FORMAT)?,
+           })
+    }
+
+    /// Updates the organisms if their interval has elapsed and returns whether they were updated.
+    fn update(&mut self, graph: &CellGraph) -> std::io::Result<bool> {
+        if let Some(reports) = self.tracker.update(graph) {
+            for report in reports {
+                self.rows.write(&report.to_row(self.tracker.tick()))?;
+            }
+            self.rows.flush()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// The cells of the largest organism found at the last update.
+    fn largest(&self) -> Option<&[petgraph::stable_graph::NodeIndex<u32>]> {
+        self.tracker
+            .organisms()
+            .iter()
+            .max_by_key(|o| o.nodes.len())
+            .map(|o| &o.nodes[..])
+    }
+}
+
 pub struct CellContainer {
+    /// A number which uniquely identifies the cell for the whole run.
+    pub id: usize,
+    /// The species the cell was last assigned to.
+    pub species: Option<usize>,
     pub cell: cell::Cell,
     /// The current delta.
     pub delta: Option<cell::Delta>,
 }
+
+impl CellContainer {
+    pub fn new(cell: cell::Cell) -> CellContainer {
+        use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+        static NEXT_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+        CellContainer {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            species: None,
+            cell: cell,
+            delta: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Isaac64Rng, SeedableRng};
+    use auxillary::{random_point, spawn_cell};
+    use {chemical, flow, ledger, obstacle, spawn, step_with, CellGraph, Environment, FLOWS, SEED, SPAWN_POLICY};
+
+    const TICKS: usize = 300;
+    const DIVISION_TICKS: usize = 25;
+
+    #[test]
+    fn energy_balances_every_tick() {
+        let mut rng = Isaac64Rng::from_seed(&SEED);
+        let mut graph = CellGraph::new();
+        for _ in 0..8 {
+            let position = random_point(&mut rng);
+            spawn_cell(&mut graph, position, None, &mut rng);
+        }
+        let scenario = obstacle::Scenario::default();
+        let mut environment = Environment {
+            spawner: spawn::Spawner::new(SPAWN_POLICY, Default::default()),
+            chemicals: chemical::ChemicalField::new(&scenario),
+            flow: flow::FlowField::new(FLOWS),
+            scenario: scenario,
+        };
+        let mut divisions = 0;
+        for tick in 0..TICKS {
+            let before = ledger::total_energy(&graph);
+            // Make sure some cells divide whatever their brains decide.
+            let counts = step_with(&mut graph, &mut environment, &mut rng, |graph| if tick % DIVISION_TICKS == 0 {
+                if let Some(nix) = graph.node_indices().next() {
+                    graph[nix].delta.as_mut().unwrap().divide = true;
+                }
+            });
+            counts.energy.check_balance(before, ledger::total_energy(&graph));
+            divisions += counts.divisions;
+        }
+        assert!(divisions > 0);
+    }
+}
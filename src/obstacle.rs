@@ -0,0 +1,208 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use nalgebra::{Dot, Norm, Vector2};
+use petgraph::stable_graph::NodeIndex;
+use serde_json;
+use zoom::Toroid;
+use auxillary::area_box;
+use cell::Cell;
+use CellGraph;
+
+/// A static shape in the world which cells can't pass through and connections can't cross.
+/// Points are `[x, y]` in world coordinates.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Obstacle {
+    /// A wall with no thickness.
+    Segment { from: [f64; 2], to: [f64; 2] },
+    Circle { center: [f64; 2], radius: f64 },
+    /// A filled polygon; the last point joins back up with the first.
+    Polygon { points: Vec<[f64; 2]> },
+}
+
+impl Obstacle {
+    /// Finds the point on the boundary closest to a position and whether the position is inside.
+    /// The position is moved to the copy of the world nearest the obstacle first.
+    fn closest(&self, position: Vector2<f64>) -> (Vector2<f64>, Vector2<f64>, bool) {
+        match *self {
+            Obstacle::Segment { from, to } => {
+                let (from, to) = (vector(from), vector(to));
+                let position = from + area_box().wrap_delta(position - from);
+                (position, closest_on_segment(position, from, to), false)
+            }
+            Obstacle::Circle { center, radius } => {
+                let center = vector(center);
+                let delta = area_box().wrap_delta(position - center);
+                let distance = delta.norm();
+                let direction = if distance > 0.0 { delta / distance } else { Vector2::new(1.0, 0.0) };
+                (center + delta, center + direction * radius, distance < radius)
+            }
+            Obstacle::Polygon { ref points } => {
+                let first = points.first().map(|&p| vector(p)).unwrap_or(position);
+                let position = first + area_box().wrap_delta(position - first);
+                let closest = edges(points)
+                    .map(|(a, b)| closest_on_segment(position, a, b))
+                    .fold(None, |best: Option<Vector2<f64>>, p| match best {
+                        Some(bp) if (bp - position).norm_squared() <= (p - position).norm_squared() => best,
+                        _ => Some(p),
+                    })
+                    .unwrap_or(first);
+                // Count the crossings of a ray going in the positive x direction.
+                let inside = edges(points)
+                    .filter(|&(a, b)| (a.y > position.y) != (b.y > position.y))
+                    .filter(|&(a, b)| position.x < a.x + (position.y - a.y) / (b.y - a.y) * (b.x - a.x))
+                    .count() % 2 == 1;
+                (position, closest, inside)
+            }
+        }
+    }
+
+    /// Determines if the straight line between two points passes through the obstacle.
+    fn blocks(&self, from: Vector2<f64>, to: Vector2<f64>) -> bool {
+        match *self {
+            Obstacle::Segment { from: a, to: b } => segments_intersect(from, to, vector(a), vector(b)),
+            Obstacle::Circle { center, radius } => {
+                (closest_on_segment(vector(center), from, to) - vector(center)).norm_squared() < radius * radius
+            }
+            Obstacle::Polygon { ref points } => {
+                edges(points).any(|(a, b)| segments_intersect(from, to, a, b)) || self.closest(from).2
+            }
+        }
+    }
+}
+
+/// Every obstacle in the world.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Scenario {
+    pub obstacles: Vec<Obstacle>,
+}
+
+impl Scenario {
+    /// Loads a scenario from a JSON file. A file that doesn't exist is a scenario without obstacles.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Scenario> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Ok(Scenario::default());
+        }
+        let file = BufReader::new(File::open(path)?);
+        serde_json::from_reader(file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.obstacles.is_empty()
+    }
+
+    /// Moves cells which passed through an obstacle since they were at their previous positions back and stops
+    /// them, then moves cells which are inside or within `margin` of an obstacle back out and stops them moving
+    /// further in.
+    pub fn resolve<F>(&self,
+                      graph: &mut CellGraph,
+                      nodes: &[NodeIndex<u32>],
+                      previous: &[Vector2<f64>],
+                      margin: F)
+        where F: Fn(&Cell) -> f64
+    {
+        for (&nix, &previous) in nodes.iter().zip(previous) {
+            if self.blocks(previous, graph[nix].cell.position()) {
+                graph[nix].cell.set_motion(previous, Vector2::new(0.0, 0.0));
+            }
+            let margin = margin(&graph[nix].cell);
+            for obstacle in &self.obstacles {
+                let (position, closest, inside) = obstacle.closest(graph[nix].cell.position());
+                let delta = position - closest;
+                let distance = delta.norm();
+                if !inside && distance >= margin {
+                    continue;
+                }
+                // The outward direction, which is away from the boundary unless the cell is inside.
+                let normal = match (distance > 0.0, inside) {
+                    (true, false) => delta / distance,
+                    (true, true) => -delta / distance,
+                    (false, _) => continue,
+                };
+                let velocity = graph[nix].cell.velocity();
+                let into = velocity.dot(&normal).min(0.0);
+                graph[nix].cell.set_motion(closest + normal * margin, velocity - normal * into);
+            }
+        }
+    }
+
+    /// Determines if a position is inside any obstacle.
+    pub fn covers(&self, position: Vector2<f64>) -> bool {
+        self.obstacles.iter().any(|obstacle| obstacle.closest(position).2)
+    }
+
+    /// Determines if any obstacle lies on the shortest path between two positions.
+    pub fn blocks(&self, from: Vector2<f64>, to: Vector2<f64>) -> bool {
+        let to = from + area_box().wrap_delta(to - from);
+        self.obstacles.iter().any(|obstacle| obstacle.blocks(from, to))
+    }
+}
+
+fn vector(p: [f64; 2]) -> Vector2<f64> {
+    Vector2::new(p[0], p[1])
+}
+
+fn edges<'a>(points: &'a [[f64; 2]]) -> impl Iterator<Item = (Vector2<f64>, Vector2<f64>)> + 'a {
+    points.iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(&a, &b)| (vector(a), vector(b)))
+}
+
+fn closest_on_segment(p: Vector2<f64>, a: Vector2<f64>, b: Vector2<f64>) -> Vector2<f64> {
+    let ab = b - a;
+    let length_squared = ab.norm_squared();
+    if length_squared == 0.0 {
+        return a;
+    }
+    a + ab * ((p - a).dot(&ab) / length_squared).max(0.0).min(1.0)
+}
+
+fn segments_intersect(p0: Vector2<f64>, p1: Vector2<f64>, q0: Vector2<f64>, q1: Vector2<f64>) -> bool {
+    let cross = |a: Vector2<f64>, b: Vector2<f64>| a.x * b.y - a.y * b.x;
+    let (r, s) = (p1 - p0, q1 - q0);
+    let denominator = cross(r, s);
+    if denominator == 0.0 {
+        return false;
+    }
+    let t = cross(q0 - p0, s) / denominator;
+    let u = cross(q0 - p0, r) / denominator;
+    // A line starting right on the other segment doesn't count so cells left on a boundary can move away.
+    t > 0.0 && t <= 1.0 && u >= 0.0 && u <= 1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector2;
+    use super::{segments_intersect, Obstacle};
+
+    fn v(x: f64, y: f64) -> Vector2<f64> {
+        Vector2::new(x, y)
+    }
+
+    #[test]
+    fn segments_crossing() {
+        assert!(segments_intersect(v(0.0, -1.0), v(0.0, 1.0), v(-1.0, 0.0), v(1.0, 0.0)));
+        assert!(!segments_intersect(v(0.0, 1.0), v(0.0, 2.0), v(-1.0, 0.0), v(1.0, 0.0)));
+        assert!(!segments_intersect(v(-1.0, 1.0), v(1.0, 1.0), v(-1.0, 0.0), v(1.0, 0.0)));
+    }
+
+    #[test]
+    fn segment_endpoints() {
+        // Ending on the wall or touching one of its ends is blocked.
+        assert!(segments_intersect(v(0.0, 1.0), v(0.0, 0.0), v(-1.0, 0.0), v(1.0, 0.0)));
+        assert!(segments_intersect(v(1.0, 1.0), v(1.0, -1.0), v(-1.0, 0.0), v(1.0, 0.0)));
+        // Starting on the wall isn't, so a cell resting on it can move away.
+        assert!(!segments_intersect(v(0.0, 0.0), v(0.0, 1.0), v(-1.0, 0.0), v(1.0, 0.0)));
+    }
+
+    #[test]
+    fn polygon_inside() {
+        let triangle = Obstacle::Polygon { points: vec![[0.0, 0.0], [10.0, 5.0], [0.0, 10.0]] };
+        assert!(triangle.closest(v(2.0, 5.0)).2);
+        assert!(!triangle.closest(v(12.0, 5.0)).2);
+        // A ray passing through a vertex counts as crossing the boundary once.
+        assert!(!triangle.closest(v(-5.0, 5.0)).2);
+        assert!(!triangle.closest(v(-5.0, 0.0)).2);
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use petgraph::stable_graph::NodeIndex;
+use rand::{Isaac64Rng, Rng};
+use auxillary::connected_components;
+use kin::{mean_edge_relatedness, neighbor_repulsion_fraction};
+use stats::{measurement_rng, Measurement, Row};
+use CellGraph;
+
+/// The most pairs of cells sampled when computing the relatedness of an organism.
+const RELATEDNESS_SAMPLES: usize = 32;
+
+/// A group of connected cells which keeps its identity as cells are born, die, split off or join.
+#[derive(Clone, Debug)]
+pub struct Organism {
+    pub id: usize,
+    /// The tick at which the organism was first seen.
+    pub born: usize,
+    /// The ids of the cells in the organism.
+    pub members: Vec<usize>,
+    pub nodes: Vec<NodeIndex<u32>>,
+}
+
+/// A summary of an organism at a point in time.
+#[derive(Clone, Debug)]
+pub struct OrganismReport {
+    pub id: usize,
+    pub size: usize,
+    pub age: usize,
+    pub energy: usize,
+    /// The mean relatedness between pairs of member cells from 0 to 1.
+    pub relatedness: f64,
+    /// The mean relatedness between connected member cells from 0 to 1, if there are any connections.
+    pub edge_relatedness: Option<f64>,
+    /// The fraction of the members' repulsion which pushes on each other rather than on outsiders.
+    pub neighbor_repulsion: f64,
+}
+
+impl OrganismReport {
+    pub fn to_row(&self, tick: usize) -> Row {
+        let mut row = Row::new();
+        row.push("tick", tick as f64);
+        row.push("organism", self.id as f64);
+        row.push("size", self.size as f64);
+        row.push("age", self.age as f64);
+        row.push("energy", self.energy as f64);
+        row.push("relatedness", self.relatedness);
+        row.push("edge_relatedness", self.edge_relatedness.unwrap_or(::std::f64::NAN));
+        row.push("neighbor_repulsion", self.neighbor_repulsion);
+        row
+    }
+}
+
+/// Periodically finds the organisms in the world and matches them with the organisms found previously.
+pub struct OrganismTracker {
+    interval: usize,
+    tick: usize,
+    next_id: usize,
+    organisms: Vec<Organism>,
+    rng: Isaac64Rng,
+}
+
+impl OrganismTracker {
+    pub fn new(interval: usize) -> OrganismTracker {
+        OrganismTracker {
+            interval: interval,
+            tick: 0,
+            next_id: 0,
+            organisms: Vec::new(),
+            rng: measurement_rng(Measurement::Organisms),
+        }
+    }
+
+    pub fn tick(&self) -> usize {
+        self.tick
+    }
+
+    /// The organisms found at the last update.
+    pub fn organisms(&self) -> &[Organism] {
+        &self.organisms
+    }
+
+    /// Advances a tick and, if the interval has elapsed, updates the organisms and returns reports for each.
+    pub fn update(&mut self, graph: &CellGraph) -> Option<Vec<OrganismReport>> {
+        self.tick += 1;
+        if self.tick % self.interval != 0 {
+            return None;
+        }
+        self.match_components(graph);
+        let tick = self.tick;
+        let rng = &mut self.rng;
+        Some(self.organisms
+                 .iter()
+                 .map(|o| {
+                          OrganismReport {
+                              id: o.id,
+                              size: o.nodes.len(),
+                              age: tick - o.born,
+                              energy: o.nodes.iter().map(|&nix| graph[nix].cell.energy()).sum(),
+                              relatedness: mean_relatedness(graph, &o.nodes, rng),
+                              edge_relatedness: mean_edge_relatedness(graph, &o.nodes, rng),
+                              neighbor_repulsion: neighbor_repulsion_fraction(graph, &o.nodes),
+                          }
+                      })
+                 .collect())
+    }
+
+    /// Finds the components of the graph and gives each the id of the previous organism it overlaps most.
+    ///
+    /// When an organism splits, the largest overlapping part keeps the id. When organisms merge, the merged
+    /// organism takes the id of whichever shared the most cells with it. Every other component is a new organism.
+    fn match_components(&mut self, graph: &CellGraph) {
+        let previous_owner = self.organisms
+            .iter()
+            .enumerate()
+            .flat_map(|(ix, o)| o.members.iter().map(move |&id| (id, ix)))
+            .collect::<HashMap<_, _>>();
+
+        let components = connected_components(graph);
+        let mut overlaps = Vec::new();
+        for (cix, component) in components.iter().enumerate() {
+            let mut counts: HashMap<usize, usize> = HashMap::new();
+            for &nix in component {
+                if let Some(&oix) = previous_owner.get(&graph[nix].id) {
+                    *counts.entry(oix).or_insert(0) += 1;
+                }
+            }
+            overlaps.extend(counts.into_iter().map(|(oix, count)| (count, cix, oix)));
+        }
+        // Match the largest overlaps first; ties are broken deterministically by index.
+        overlaps.sort_by(|a, b| b.cmp(a));
+
+        let mut component_owner = vec![None; components.len()];
+        let mut organism_taken = vec![false; self.organisms.len()];
+        for (_, cix, oix) in overlaps {
+            if component_owner[cix].is_none() && !organism_taken[oix] {
+                component_owner[cix] = Some(oix);
+                organism_taken[oix] = true;
+            }
+        }
+
+        let mut organisms = Vec::with_capacity(components.len());
+        for (component, owner) in components.into_iter().zip(component_owner) {
+            let (id, born) = match owner {
+                Some(oix) => (self.organisms[oix].id, self.organisms[oix].born),
+                None => {
+                    self.next_id += 1;
+                    (self.next_id - 1, self.tick)
+                }
+            };
+            organisms.push(Organism {
+                               id: id,
+                               born: born,
+                               members: component.iter().map(|&nix| graph[nix].id).collect(),
+                               nodes: component,
+                           });
+        }
+        self.organisms = organisms;
+    }
+}
+
+/// Computes the mean relatedness between pairs of cells, sampling pairs when there are many.
+pub fn mean_relatedness<R: Rng>(graph: &CellGraph, nodes: &[NodeIndex<u32>], rng: &mut R) -> f64 {
+    if nodes.len() < 2 {
+        return 1.0;
+    }
+    let pairs = nodes.len() * (nodes.len() - 1) / 2;
+    if pairs <= RELATEDNESS_SAMPLES {
+        let mut total = 0.0;
+        for i in 0..nodes.len() {
+            for j in i + 1..nodes.len() {
+                total += graph[nodes[i]].cell.relatedness(&graph[nodes[j]].cell);
+            }
+        }
+        total / pairs as f64
+    } else {
+        let mut total = 0.0;
+        for _ in 0..RELATEDNESS_SAMPLES {
+            let i = rng.gen_range(0, nodes.len());
+            let j = (i + rng.gen_range(1, nodes.len())) % nodes.len();
+            total += graph[nodes[i]].cell.relatedness(&graph[nodes[j]].cell);
+        }
+        total / RELATEDNESS_SAMPLES as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector2;
+    use petgraph::stable_graph::NodeIndex;
+    use rand::{Isaac64Rng, SeedableRng};
+    use auxillary::spawn_cell;
+    use CellGraph;
+    use super::OrganismTracker;
+
+    fn spawn_cells(graph: &mut CellGraph, count: usize) -> Vec<NodeIndex<u32>> {
+        let mut rng = Isaac64Rng::from_seed(&[1, 2, 3, 4]);
+        (0..count)
+            .map(|i| spawn_cell(graph, Vector2::new(i as f64, 0.0), None, &mut rng))
+            .collect()
+    }
+
+    fn id_of(tracker: &OrganismTracker, nix: NodeIndex<u32>) -> usize {
+        tracker.organisms.iter().find(|o| o.nodes.contains(&nix)).unwrap().id
+    }
+
+    #[test]
+    fn split_keeps_id_on_larger_part() {
+        let mut graph = CellGraph::new();
+        let n = spawn_cells(&mut graph, 5);
+        graph.add_edge(n[0], n[1], Default::default());
+        let eix = graph.add_edge(n[1], n[2], Default::default());
+        graph.add_edge(n[2], n[3], Default::default());
+        graph.add_edge(n[3], n[4], Default::default());
+        let mut tracker = OrganismTracker::new(1);
+        tracker.match_components(&graph);
+        let id = id_of(&tracker, n[0]);
+
+        graph.remove_edge(eix);
+        tracker.match_components(&graph);
+        assert_eq!(tracker.organisms.len(), 2);
+        assert_eq!(id_of(&tracker, n[2]), id);
+        assert!(id_of(&tracker, n[0]) != id);
+    }
+
+    #[test]
+    fn merge_takes_id_with_larger_overlap() {
+        let mut graph = CellGraph::new();
+        let n = spawn_cells(&mut graph, 5);
+        graph.add_edge(n[0], n[1], Default::default());
+        graph.add_edge(n[2], n[3], Default::default());
+        graph.add_edge(n[3], n[4], Default::default());
+        let mut tracker = OrganismTracker::new(1);
+        tracker.match_components(&graph);
+        let larger = id_of(&tracker, n[2]);
+
+        graph.add_edge(n[1], n[2], Default::default());
+        tracker.match_components(&graph);
+        assert_eq!(tracker.organisms.len(), 1);
+        assert_eq!(id_of(&tracker, n[0]), larger);
+    }
+}
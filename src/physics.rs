@@ -0,0 +1,132 @@
+use nalgebra::Vector2;
+use petgraph::stable_graph::NodeIndex;
+use auxillary::area_box;
+use cell::PHYSICS_DELTA;
+use obstacle::Scenario;
+use CellGraph;
+
+/// How positions and velocities are advanced from the forces on the cells.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Integrator {
+    /// Updates the velocity and then the position from it; cheap, but unstable with stiff springs.
+    SemiImplicitEuler,
+    /// Second order and time-reversible; evaluates the forces twice per step.
+    VelocityVerlet,
+    /// Fourth order Runge-Kutta; evaluates the forces four times per step.
+    RungeKutta4,
+}
+
+/// Advances the physics of every cell by one brain tick, split into `substeps` steps of equal length.
+/// If `collisions` is set, overlapping cells are separated after every step. Cells are kept out of the
+/// scenario's obstacles, with their radius as a margin if they collide.
+///
+/// `forces` must apply the impulses on every cell for their current positions and velocities, since it is
+/// called again for every intermediate state the integrator needs.
+pub fn advance<F>(graph: &mut CellGraph,
+                  integrator: Integrator,
+                  substeps: usize,
+                  collisions: bool,
+                  scenario: &Scenario,
+                  mut forces: F)
+    where F: FnMut(&mut CellGraph)
+{
+    let delta = PHYSICS_DELTA / substeps as f64;
+    let nodes = graph.node_indices().collect::<Vec<_>>();
+    for _ in 0..substeps {
+        let previous = nodes.iter().map(|&nix| graph[nix].cell.position()).collect::<Vec<_>>();
+        match integrator {
+            Integrator::SemiImplicitEuler => {
+                forces(graph);
+                for &nix in &nodes {
+                    graph[nix].cell.update_physics(delta);
+                }
+            }
+            Integrator::VelocityVerlet => velocity_verlet(graph, &nodes, delta, &mut forces),
+            Integrator::RungeKutta4 => runge_kutta_4(graph, &nodes, delta, &mut forces),
+        }
+        if collisions {
+            resolve_collisions(graph, &nodes);
+        }
+        if !scenario.is_empty() {
+            scenario.resolve(graph,
+                             &nodes,
+                             &previous,
+                             |cell| if collisions { cell.radius() } else { 0.0 });
+        }
+    }
+}
+
+/// Moves overlapping cells apart until they touch and stops them moving further into each other.
+fn resolve_collisions(graph: &mut CellGraph, nodes: &[NodeIndex<u32>]) {
+    use nalgebra::{Dot, Norm};
+    use zoom::Toroid;
+    for (i, &a) in nodes.iter().enumerate() {
+        for &b in &nodes[i + 1..] {
+            let (pa, pb) = (graph[a].cell.position(), graph[b].cell.position());
+            let delta = area_box().wrap_delta(pb - pa);
+            let distance = delta.norm();
+            let overlap = graph[a].cell.radius() + graph[b].cell.radius() - distance;
+            if overlap <= 0.0 || distance == 0.0 {
+                continue;
+            }
+            let normal = delta / distance;
+            let (va, vb) = (graph[a].cell.velocity(), graph[b].cell.velocity());
+            // Every cell has the same mass, so both take half of the correction.
+            let approach = (va - vb).dot(&normal).max(0.0);
+            graph[a].cell.set_motion(pa - normal * (0.5 * overlap), va - normal * (0.5 * approach));
+            graph[b].cell.set_motion(pb + normal * (0.5 * overlap), vb + normal * (0.5 * approach));
+        }
+    }
+}
+
+fn accelerations<F>(graph: &mut CellGraph, nodes: &[NodeIndex<u32>], forces: &mut F) -> Vec<Vector2<f64>>
+    where F: FnMut(&mut CellGraph)
+{
+    forces(graph);
+    nodes.iter().map(|&nix| graph[nix].cell.take_acceleration()).collect()
+}
+
+fn velocity_verlet<F>(graph: &mut CellGraph, nodes: &[NodeIndex<u32>], delta: f64, forces: &mut F)
+    where F: FnMut(&mut CellGraph)
+{
+    let before = accelerations(graph, nodes, forces);
+    for (&nix, &a) in nodes.iter().zip(&before) {
+        let (x, v) = (graph[nix].cell.position(), graph[nix].cell.velocity());
+        graph[nix].cell.set_motion(x + v * delta + a * (0.5 * delta * delta), v);
+    }
+    let after = accelerations(graph, nodes, forces);
+    for ((&nix, &a0), &a1) in nodes.iter().zip(&before).zip(&after) {
+        let (x, v) = (graph[nix].cell.position(), graph[nix].cell.velocity());
+        graph[nix].cell.set_motion(x, v + (a0 + a1) * (0.5 * delta));
+    }
+}
+
+fn runge_kutta_4<F>(graph: &mut CellGraph, nodes: &[NodeIndex<u32>], delta: f64, forces: &mut F)
+    where F: FnMut(&mut CellGraph)
+{
+    let start = nodes.iter()
+        .map(|&nix| (graph[nix].cell.position(), graph[nix].cell.velocity()))
+        .collect::<Vec<_>>();
+    let mut stage_v = start.iter().map(|&(_, v)| v).collect::<Vec<_>>();
+    let mut stage_a = accelerations(graph, nodes, forces);
+    let mut sum_v = stage_v.clone();
+    let mut sum_a = stage_a.clone();
+    // Each later stage starts from the beginning of the step using the derivatives of the previous stage.
+    for &(fraction, weight) in &[(0.5, 2.0), (0.5, 2.0), (1.0, 1.0)] {
+        for (i, &nix) in nodes.iter().enumerate() {
+            let (x0, v0) = start[i];
+            let x = x0 + stage_v[i] * (fraction * delta);
+            stage_v[i] = v0 + stage_a[i] * (fraction * delta);
+            graph[nix].cell.set_motion(x, stage_v[i]);
+        }
+        stage_a = accelerations(graph, nodes, forces);
+        for i in 0..nodes.len() {
+            sum_v[i] = sum_v[i] + stage_v[i] * weight;
+            sum_a[i] = sum_a[i] + stage_a[i] * weight;
+        }
+    }
+    for (i, &nix) in nodes.iter().enumerate() {
+        let (x0, v0) = start[i];
+        graph[nix].cell.set_motion(x0 + sum_v[i] * (delta / 6.0), v0 + sum_a[i] * (delta / 6.0));
+    }
+}
@@ -0,0 +1,187 @@
+use gg::render2::{Node, QBezier};
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::PathBuf;
+use auxillary::area_box;
+
+/// How many line segments each quadratic bezier is flattened into.
+const BEZIER_SEGMENTS: usize = 8;
+
+/// An RGB image which the world can be drawn into without a GPU.
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 3]>,
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32) -> Canvas {
+        Canvas {
+            width: width,
+            height: height,
+            pixels: vec![[0.0; 3]; (width * height) as usize],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for pixel in &mut self.pixels {
+            *pixel = [0.0; 3];
+        }
+    }
+
+    /// Draws curves given in world coordinates, the same as `render_qbeziers_flat`.
+    pub fn draw_qbeziers(&mut self, qbeziers: &[QBezier]) {
+        for qb in qbeziers {
+            let point = |t: f32| {
+                let u = 1.0 - t;
+                [u * u * qb.position0[0] + 2.0 * u * t * qb.position1[0] + t * t * qb.position2[0],
+                 u * u * qb.position0[1] + 2.0 * u * t * qb.position1[1] + t * t * qb.position2[1]]
+            };
+            for segment in 0..BEZIER_SEGMENTS {
+                let t0 = segment as f32 / BEZIER_SEGMENTS as f32;
+                let t1 = (segment + 1) as f32 / BEZIER_SEGMENTS as f32;
+                let tm = (t0 + t1) * 0.5;
+                self.draw_segment(point(t0),
+                                  point(t1),
+                                  lerp(qb.inner_radius0, qb.inner_radius1, tm),
+                                  lerp(qb.falloff_radius0, qb.falloff_radius1, tm),
+                                  lerp(qb.falloff0, qb.falloff1, tm),
+                                  lerp_color(qb.inner_color0, qb.inner_color1, tm),
+                                  lerp_color(qb.falloff_color0, qb.falloff_color1, tm));
+            }
+        }
+    }
+
+    /// Draws edges given as pairs of nodes in world coordinates, the same as `render_edges_round`.
+    pub fn draw_edges(&mut self, nodes: &[Node]) {
+        for pair in nodes.chunks(2).filter(|pair| pair.len() == 2) {
+            let (n0, n1) = (&pair[0], &pair[1]);
+            self.draw_segment(n0.position,
+                              n1.position,
+                              (n0.inner_radius + n1.inner_radius) * 0.5,
+                              (n0.falloff_radius + n1.falloff_radius) * 0.5,
+                              (n0.falloff + n1.falloff) * 0.5,
+                              lerp_color(n0.inner_color, n1.inner_color, 0.5),
+                              lerp_color(n0.falloff_color, n1.falloff_color, 0.5));
+        }
+    }
+
+    /// Converts the canvas into 8-bit RGB bytes.
+    pub fn to_rgb8(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 3);
+        for p in &self.pixels {
+            for &c in p {
+                bytes.push((c.max(0.0).min(1.0) * 255.0) as u8);
+            }
+        }
+        bytes
+    }
+
+    fn to_pixel(&self, p: [f32; 2]) -> [f32; 2] {
+        let offset = area_box().offset;
+        [(p[0] / offset.x as f32 + 1.0) * 0.5 * self.width as f32,
+         (1.0 - p[1] / offset.y as f32) * 0.5 * self.height as f32]
+    }
+
+    fn to_pixel_radius(&self, r: f32) -> f32 {
+        r / area_box().offset.y as f32 * 0.5 * self.height as f32
+    }
+
+    fn draw_segment(&mut self,
+                    p0: [f32; 2],
+                    p1: [f32; 2],
+                    inner_radius: f32,
+                    falloff_radius: f32,
+                    falloff: f32,
+                    inner_color: [f32; 4],
+                    falloff_color: [f32; 4]) {
+        let p0 = self.to_pixel(p0);
+        let p1 = self.to_pixel(p1);
+        let inner_radius = self.to_pixel_radius(inner_radius);
+        let falloff_radius = self.to_pixel_radius(falloff_radius).max(inner_radius).max(0.5);
+
+        let min_x = (p0[0].min(p1[0]) - falloff_radius).floor().max(0.0) as i64;
+        let max_x = (p0[0].max(p1[0]) + falloff_radius).ceil().min(self.width as f32 - 1.0) as i64;
+        let min_y = (p0[1].min(p1[1]) - falloff_radius).floor().max(0.0) as i64;
+        let max_y = (p0[1].max(p1[1]) + falloff_radius).ceil().min(self.height as f32 - 1.0) as i64;
+
+        for y in min_y..max_y + 1 {
+            for x in min_x..max_x + 1 {
+                let d = segment_distance([x as f32 + 0.5, y as f32 + 0.5], p0, p1);
+                let color = if d < inner_radius {
+                    inner_color
+                } else if d < falloff_radius {
+                    let t = (d - inner_radius) / (falloff_radius - inner_radius);
+                    let intensity = (1.0 - t).powf(1.0 / falloff.max(0.01));
+                    [falloff_color[0] * intensity,
+                     falloff_color[1] * intensity,
+                     falloff_color[2] * intensity,
+                     falloff_color[3]]
+                } else {
+                    continue;
+                };
+                let pixel = &mut self.pixels[(y as u32 * self.width + x as u32) as usize];
+                // Max blending keeps the joints of flattened curves from doubling up in brightness.
+                for c in 0..3 {
+                    pixel[c] = pixel[c].max(color[c] * color[3]);
+                }
+            }
+        }
+    }
+}
+
+/// Writes numbered PNG frames into a directory.
+pub struct FrameWriter {
+    directory: PathBuf,
+    canvas: Canvas,
+    frame: usize,
+}
+
+impl FrameWriter {
+    pub fn new<P: Into<PathBuf>>(directory: P, width: u32, height: u32) -> FrameWriter {
+        FrameWriter {
+            directory: directory.into(),
+            canvas: Canvas::new(width, height),
+            frame: 0,
+        }
+    }
+
+    /// Draws the circles and edges and writes them out as the next frame.
+    pub fn write_frame(&mut self, qbeziers: &[QBezier], edges: &[Node]) -> io::Result<()> {
+        use png::HasParameters;
+        self.canvas.clear();
+        self.canvas.draw_qbeziers(qbeziers);
+        self.canvas.draw_edges(edges);
+
+        fs::create_dir_all(&self.directory)?;
+        let file = File::create(self.directory.join(format!("frame_{:06}.png", self.frame)))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.canvas.width, self.canvas.height);
+        encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.canvas.to_rgb8())?;
+        self.frame += 1;
+        Ok(())
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn lerp_color(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    [lerp(a[0], b[0], t), lerp(a[1], b[1], t), lerp(a[2], b[2], t), lerp(a[3], b[3], t)]
+}
+
+fn segment_distance(p: [f32; 2], a: [f32; 2], b: [f32; 2]) -> f32 {
+    let ab = [b[0] - a[0], b[1] - a[1]];
+    let ap = [p[0] - a[0], p[1] - a[1]];
+    let len_squared = ab[0] * ab[0] + ab[1] * ab[1];
+    let t = if len_squared > 0.0 {
+        ((ap[0] * ab[0] + ap[1] * ab[1]) / len_squared).max(0.0).min(1.0)
+    } else {
+        0.0
+    };
+    let dx = ap[0] - ab[0] * t;
+    let dy = ap[1] - ab[1] * t;
+    (dx * dx + dy * dy).sqrt()
+}
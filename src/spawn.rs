@@ -0,0 +1,115 @@
+use rand::Rng;
+use nalgebra::Vector2;
+use auxillary::{area_box, random_point, spawn_cell};
+use library::GenomeLibrary;
+use CellGraph;
+
+/// Decides how often new cells are spawned.
+#[derive(Copy, Clone, Debug)]
+pub enum SpawnRate {
+    /// Spawn a cell with this probability every tick.
+    Fixed(f64),
+    /// Spawn a cell with this probability every tick, but only while the population is below the threshold.
+    BelowPopulation { threshold: usize, probability: f64 },
+    /// Spawn this many cells on the first tick and none afterwards.
+    InitialBurst(usize),
+}
+
+/// A rectangular region of the world given by its center and half of its size.
+#[derive(Copy, Clone, Debug)]
+pub struct SpawnRegion {
+    pub center: [f64; 2],
+    pub half_size: [f64; 2],
+}
+
+/// Decides where new cells are spawned.
+#[derive(Copy, Clone, Debug)]
+pub enum SpawnLocation {
+    /// Anywhere in the world, uniformly.
+    Anywhere,
+    /// Uniformly in one of the regions, each chosen with equal probability.
+    Regions(&'static [SpawnRegion]),
+}
+
+/// Decides where the genomes of new cells come from.
+#[derive(Copy, Clone, Debug)]
+pub enum SpawnGenomes {
+    /// Every cell gets a new random genome.
+    Random,
+    /// Every cell gets a genome from the library, falling back to random ones if it is empty.
+    Library,
+    /// Cells get a genome from the library with this probability and a random genome otherwise.
+    Mixed(f64),
+}
+
+/// Determines how new cells are injected into the world.
+#[derive(Copy, Clone, Debug)]
+pub struct SpawnPolicy {
+    pub rate: SpawnRate,
+    pub location: SpawnLocation,
+    pub genomes: SpawnGenomes,
+}
+
+/// Spawns cells according to a `SpawnPolicy`.
+pub struct Spawner {
+    policy: SpawnPolicy,
+    library: GenomeLibrary,
+    tick: usize,
+}
+
+impl Spawner {
+    pub fn new(policy: SpawnPolicy, library: GenomeLibrary) -> Spawner {
+        Spawner {
+            policy: policy,
+            library: library,
+            tick: 0,
+        }
+    }
+
+    /// Spawns this tick's cells and returns how many were added.
+    pub fn spawn<R: Rng>(&mut self, graph: &mut CellGraph, rng: &mut R) -> usize {
+        let count = match self.policy.rate {
+            SpawnRate::Fixed(probability) => (rng.next_f64() < probability) as usize,
+            SpawnRate::BelowPopulation { threshold, probability } => {
+                (graph.node_count() < threshold && rng.next_f64() < probability) as usize
+            }
+            SpawnRate::InitialBurst(count) => if self.tick == 0 { count } else { 0 },
+        };
+        self.tick += 1;
+
+        for _ in 0..count {
+            let position = self.position(rng);
+            let genome = match self.policy.genomes {
+                SpawnGenomes::Random => None,
+                SpawnGenomes::Library => self.library.choose(rng),
+                SpawnGenomes::Mixed(ratio) => {
+                    if rng.next_f64() < ratio {
+                        self.library.choose(rng)
+                    } else {
+                        None
+                    }
+                }
+            };
+            spawn_cell(graph, position, genome, rng);
+        }
+        count
+    }
+
+    fn position<R: Rng>(&self, rng: &mut R) -> Vector2<f64> {
+        use zoom::Toroid;
+        match self.policy.location {
+            SpawnLocation::Anywhere => random_point(rng),
+            SpawnLocation::Regions(regions) => {
+                match rng.choose(regions) {
+                    Some(region) => {
+                        let mut central_rand = || 2.0 * rng.next_f64() - 1.0;
+                        let point = Vector2::new(region.center[0] + region.half_size[0] * central_rand(),
+                                                 region.center[1] + region.half_size[1] * central_rand());
+                        area_box().wrap_position(point)
+                    }
+                    None => random_point(rng),
+                }
+            }
+        }
+    }
+}
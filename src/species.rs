@@ -0,0 +1,124 @@
+use rand::{Isaac64Rng, Rng};
+use boolinator::Boolinator;
+use cell::Genome;
+use stats::{measurement_rng, Measurement, Row};
+use CellGraph;
+
+/// How related a cell must be to a species' representative to belong to it, from 0 to 1.
+const SPECIES_RELATEDNESS_THRESHOLD: f64 = 0.7;
+
+/// A cluster of cells with similar genomes.
+#[derive(Clone, Debug)]
+pub struct Species {
+    pub id: usize,
+    /// The tick at which the species was first seen.
+    pub born: usize,
+    /// The species the founding cell previously belonged to, if any.
+    pub parent: Option<usize>,
+    pub size: usize,
+    /// The genome cells are compared against to decide if they belong to the species.
+    representative: Genome,
+}
+
+/// Periodically clusters cells into species and keeps the species ids stable over time.
+///
+/// Each species has a representative genome. A cell stays in its species while it is related enough to the
+/// representative; otherwise it joins the most related species that will take it or founds a new one.
+/// Representatives are replaced by a random member at each update so species follow their lineages as they drift.
+pub struct SpeciesTracker {
+    interval: usize,
+    tick: usize,
+    next_id: usize,
+    species: Vec<Species>,
+    rng: Isaac64Rng,
+}
+
+impl SpeciesTracker {
+    pub fn new(interval: usize) -> SpeciesTracker {
+        SpeciesTracker {
+            interval: interval,
+            tick: 0,
+            next_id: 0,
+            species: Vec::new(),
+            rng: measurement_rng(Measurement::Species),
+        }
+    }
+
+    /// Advances a tick and, if the interval has elapsed, assigns every cell a species and returns a row for
+    /// every living species and every species that went extinct.
+    pub fn update(&mut self, graph: &mut CellGraph) -> Option<Vec<Row>> {
+        self.tick += 1;
+        if self.tick % self.interval != 0 {
+            return None;
+        }
+
+        let mut members = vec![Vec::new(); self.species.len()];
+        let mut births = Vec::new();
+        for nix in graph.node_indices().collect::<Vec<_>>() {
+            let previous = graph[nix].species;
+            let current = {
+                let genome = graph[nix].cell.genome();
+                let relatedness = |species: &Species| genome.relatedness(&species.representative);
+                // Staying in the current species is checked first so ids are stable and cheap to keep.
+                let stays = previous.and_then(|id| self.species.iter().position(|s| s.id == id))
+                    .and_then(|six| (relatedness(&self.species[six]) >= SPECIES_RELATEDNESS_THRESHOLD).as_some(six));
+                stays.or_else(|| {
+                    self.species
+                        .iter()
+                        .map(&relatedness)
+                        .enumerate()
+                        .filter(|&(_, r)| r >= SPECIES_RELATEDNESS_THRESHOLD)
+                        .fold(None, |best: Option<(usize, f64)>, (six, r)| match best {
+                            Some((_, br)) if br >= r => best,
+                            _ => Some((six, r)),
+                        })
+                        .map(|(six, _)| six)
+                })
+            };
+            let six = match current {
+                Some(six) => six,
+                None => {
+                    self.species.push(Species {
+                                          id: self.next_id,
+                                          born: self.tick,
+                                          parent: previous,
+                                          size: 0,
+                                          representative: graph[nix].cell.genome().clone(),
+                                      });
+                    members.push(Vec::new());
+                    births.push(self.next_id);
+                    self.next_id += 1;
+                    self.species.len() - 1
+                }
+            };
+            members[six].push(nix);
+            graph[nix].species = Some(self.species[six].id);
+        }
+
+        let mut rows = Vec::new();
+        let mut living = Vec::new();
+        for (mut species, members) in self.species.drain(..).zip(members) {
+            species.size = members.len();
+            let born = births.contains(&species.id);
+            rows.push(species_row(self.tick, &species, born, species.size == 0));
+            if let Some(&nix) = self.rng.choose(&members) {
+                species.representative = graph[nix].cell.genome().clone();
+                living.push(species);
+            }
+        }
+        self.species = living;
+        Some(rows)
+    }
+}
+
+fn species_row(tick: usize, species: &Species, born: bool, extinct: bool) -> Row {
+    let mut row = Row::new();
+    row.push("tick", tick as f64);
+    row.push("species", species.id as f64);
+    row.push("parent", species.parent.map(|id| id as f64).unwrap_or(-1.0));
+    row.push("size", species.size as f64);
+    row.push("age", (tick - species.born) as f64);
+    row.push("born", born as usize as f64);
+    row.push("extinct", extinct as usize as f64);
+    row
+}
@@ -0,0 +1,261 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use rand::{Isaac64Rng, SeedableRng};
+use auxillary::connected_components;
+use diversity::Diversity;
+use kin::{KinHistograms, RELATEDNESS_BINS};
+use cell::{CHROMOSOME_COUNT, CHROMOSOME_NAMES};
+use ledger::EnergyLedger;
+use CellGraph;
+
+/// The events that happened during a tick or a span of ticks.
+#[derive(Clone, Debug, Default)]
+pub struct TickCounts {
+    /// Cells that were spawned or divided.
+    pub births: usize,
+    pub deaths: usize,
+    /// Cells that were spawned into the world from nothing.
+    pub spawns: usize,
+    pub divisions: usize,
+    pub severs: usize,
+    pub energy: EnergyLedger,
+    /// The cycles each chromosome executed in the order of `CHROMOSOME_NAMES`.
+    pub cycles: [usize; CHROMOSOME_COUNT],
+}
+
+impl TickCounts {
+    pub fn accumulate(&mut self, other: &TickCounts) {
+        self.births += other.births;
+        self.deaths += other.deaths;
+        self.spawns += other.spawns;
+        self.divisions += other.divisions;
+        self.severs += other.severs;
+        self.energy.accumulate(&other.energy);
+        for (total, &cycles) in self.cycles.iter_mut().zip(other.cycles.iter()) {
+            *total += cycles;
+        }
+    }
+}
+
+/// The format the statistics are written in.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StatsFormat {
+    /// Comma separated values with a header row.
+    Csv,
+    /// One JSON object per line.
+    JsonLines,
+}
+
+/// A single row of named statistics.
+#[derive(Clone, Debug, Default)]
+pub struct Row {
+    columns: Vec<(String, f64)>,
+}
+
+impl Row {
+    pub fn new() -> Row {
+        Row::default()
+    }
+
+    pub fn push<S: Into<String>>(&mut self, name: S, value: f64) {
+        self.columns.push((name.into(), value));
+    }
+
+    fn write_csv_header<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let names = self.columns.iter().map(|&(ref name, _)| name.as_str()).collect::<Vec<_>>();
+        writeln!(w, "{}", names.join(","))
+    }
+
+    fn write_csv<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let values = self.columns.iter().map(|&(_, value)| value.to_string()).collect::<Vec<_>>();
+        writeln!(w, "{}", values.join(","))
+    }
+
+    fn write_json<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let fields = self.columns
+            .iter()
+            .map(|&(ref name, value)| if value.is_finite() {
+                     format!("\"{}\":{}", name, value)
+                 } else {
+                     format!("\"{}\":null", name)
+                 })
+            .collect::<Vec<_>>();
+        writeln!(w, "{{{}}}", fields.join(","))
+    }
+}
+
+/// Writes rows in a `StatsFormat`.
+pub struct RowWriter<W> {
+    writer: W,
+    format: StatsFormat,
+    header_written: bool,
+}
+
+impl RowWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P, format: StatsFormat) -> io::Result<RowWriter<BufWriter<File>>> {
+        Ok(RowWriter::new(BufWriter::new(File::create(path)?), format))
+    }
+}
+
+impl<W: Write> RowWriter<W> {
+    pub fn new(writer: W, format: StatsFormat) -> RowWriter<W> {
+        RowWriter {
+            writer: writer,
+            format: format,
+            header_written: false,
+        }
+    }
+
+    /// Writes a row. In CSV the first row written determines the header.
+    pub fn write(&mut self, row: &Row) -> io::Result<()> {
+        match self.format {
+            StatsFormat::Csv => {
+                if !self.header_written {
+                    row.write_csv_header(&mut self.writer)?;
+                    self.header_written = true;
+                }
+                row.write_csv(&mut self.writer)
+            }
+            StatsFormat::JsonLines => row.write_json(&mut self.writer),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Seeds every measurement's random number generator along with the measurement itself.
+const MEASUREMENT_SEED: u64 = 8;
+
+/// The measurements which sample the world at random.
+#[derive(Copy, Clone, Debug)]
+pub enum Measurement {
+    Stats,
+    Organisms,
+    Species,
+}
+
+/// Creates the random number generator a measurement samples with. Measurements never draw from the
+/// simulation's generator, so taking them doesn't change how a seeded run unfolds, and each measurement gets
+/// its own seed so enabling one doesn't change what another samples.
+pub fn measurement_rng(measurement: Measurement) -> Isaac64Rng {
+    Isaac64Rng::from_seed(&[MEASUREMENT_SEED, measurement as u64])
+}
+
+/// Periodically appends a row of statistics about the world to an output.
+pub struct StatsLogger<W> {
+    rows: RowWriter<W>,
+    interval: usize,
+    tick: usize,
+    counts: TickCounts,
+    rng: Isaac64Rng,
+}
+
+impl StatsLogger<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P,
+                                  format: StatsFormat,
+                                  interval: usize)
+                                  -> io::Result<StatsLogger<BufWriter<File>>> {
+        Ok(StatsLogger::new(BufWriter::new(File::create(path)?), format, interval))
+    }
+}
+
+impl<W: Write> StatsLogger<W> {
+    pub fn new(writer: W, format: StatsFormat, interval: usize) -> StatsLogger<W> {
+        StatsLogger {
+            rows: RowWriter::new(writer, format),
+            interval: interval,
+            tick: 0,
+            counts: TickCounts::default(),
+            rng: measurement_rng(Measurement::Stats),
+        }
+    }
+
+    /// Records the events of a tick and writes a row if the interval has elapsed.
+    pub fn record(&mut self, graph: &CellGraph, counts: &TickCounts) -> io::Result<()> {
+        self.tick += 1;
+        self.counts.accumulate(counts);
+        if self.tick % self.interval != 0 {
+            return Ok(());
+        }
+        let row = collect_row(self.tick, graph, &self.counts, &mut self.rng);
+        self.counts = TickCounts::default();
+        self.rows.write(&row)?;
+        self.rows.flush()
+    }
+}
+
+/// Gathers the statistics of the world into a row.
+fn collect_row(tick: usize, graph: &CellGraph, counts: &TickCounts, rng: &mut Isaac64Rng) -> Row {
+    let mut row = Row::new();
+    let population = graph.node_count();
+    let mean = |total: f64| if population == 0 {
+        0.0
+    } else {
+        total / population as f64
+    };
+    row.push("tick", tick as f64);
+    row.push("population", population as f64);
+    row.push("births", counts.births as f64);
+    row.push("deaths", counts.deaths as f64);
+    row.push("spawns", counts.spawns as f64);
+    row.push("divisions", counts.divisions as f64);
+    row.push("severs", counts.severs as f64);
+    counts.energy.push_columns(&mut row);
+    for (name, &cycles) in CHROMOSOME_NAMES.iter().zip(counts.cycles.iter()) {
+        row.push(format!("{}_cycles", name), cycles as f64);
+    }
+
+    let energies = graph.node_indices().map(|nix| graph[nix].cell.energy()).collect::<Vec<_>>();
+    let total_energy = energies.iter().sum::<usize>();
+    row.push("total_energy", total_energy as f64);
+    row.push("mean_energy", mean(total_energy as f64));
+    row.push("max_energy",
+             energies.iter().cloned().max().unwrap_or(0) as f64);
+    row.push("mean_age",
+             mean(graph.node_indices().map(|nix| graph[nix].cell.age() as f64).sum::<f64>()));
+
+    let mut total_lengths = [0; CHROMOSOME_COUNT];
+    for nix in graph.node_indices() {
+        for (total, length) in total_lengths.iter_mut().zip(graph[nix].cell.chromosome_lengths().iter()) {
+            *total += *length;
+        }
+    }
+    for (name, &total) in CHROMOSOME_NAMES.iter().zip(total_lengths.iter()) {
+        row.push(format!("mean_{}_len", name), mean(total as f64));
+    }
+    row.push("mean_lambda",
+             mean(graph.node_indices().map(|nix| graph[nix].cell.lambda()).sum()));
+
+    let components = connected_components(graph);
+    row.push("edges", graph.edge_count() as f64);
+    row.push("components", components.len() as f64);
+    row.push("mean_component_size",
+             if components.is_empty() {
+                 0.0
+             } else {
+                 population as f64 / components.len() as f64
+             });
+
+    let diversity = Diversity::measure(graph, rng);
+    row.push("distinct_genotypes", diversity.distinct_genotypes as f64);
+    for (name, &distance) in CHROMOSOME_NAMES.iter().zip(diversity.mean_distances.iter()) {
+        row.push(format!("mean_{}_distance", name), distance);
+    }
+    for (name, &entropy) in CHROMOSOME_NAMES.iter().zip(diversity.entropies.iter()) {
+        row.push(format!("{}_entropy", name), entropy);
+    }
+
+    let kin = KinHistograms::measure(graph, rng);
+    row.push("connected_relatedness", kin.connected_mean);
+    row.push("unconnected_relatedness", kin.unconnected_mean);
+    for bin in 0..RELATEDNESS_BINS {
+        row.push(format!("connected_relatedness_bin{}", bin), kin.connected[bin] as f64);
+    }
+    for bin in 0..RELATEDNESS_BINS {
+        row.push(format!("unconnected_relatedness_bin{}", bin), kin.unconnected[bin] as f64);
+    }
+    row
+}
@@ -0,0 +1,78 @@
+use std::io::{self, Write};
+use std::fs::File;
+use std::path::Path;
+use petgraph::visit::{EdgeRef, IntoEdgeReferences};
+use nalgebra::Vector2;
+use zoom::Toroid;
+use auxillary::area_box;
+use color::{self, Palette};
+use CellGraph;
+use {drawn_radius, CIRCLE_SCALE};
+
+const EDGE_WIDTH_RATIO: f64 = 0.3;
+
+/// Writes the graph as an SVG image to a file.
+pub fn save_svg<P: AsRef<Path>>(graph: &CellGraph, palette: &Palette, path: P) -> io::Result<()> {
+    let mut file = io::BufWriter::new(File::create(path)?);
+    write_svg(graph, palette, &mut file)
+}
+
+/// Writes the graph as an SVG image with cells as circles and edges as lines.
+///
+/// Edges which wrap around the border of the world are drawn as two lines, each leaving through the border.
+pub fn write_svg<W: Write>(graph: &CellGraph, palette: &Palette, w: &mut W) -> io::Result<()> {
+    let area = area_box();
+    let radius = CIRCLE_SCALE as f64 * area.offset.y;
+    writeln!(w,
+             r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+             area.origin.x - area.offset.x,
+             area.origin.y - area.offset.y,
+             2.0 * area.offset.x,
+             2.0 * area.offset.y)?;
+    writeln!(w,
+             r#"<rect x="{}" y="{}" width="{}" height="{}" fill="black"/>"#,
+             area.origin.x - area.offset.x,
+             area.origin.y - area.offset.y,
+             2.0 * area.offset.x,
+             2.0 * area.offset.y)?;
+    // Flip the y axis so the image matches the live view.
+    writeln!(w, r#"<g transform="scale(1,-1)">"#)?;
+
+    writeln!(w,
+             r#"<g stroke="{}" stroke-width="{}" stroke-linecap="round">"#,
+             color::hex_color(color::EDGE_COLOR),
+             radius * EDGE_WIDTH_RATIO)?;
+    for er in graph.edge_references() {
+        let p0 = graph[er.source()].cell.position();
+        let p1 = graph[er.target()].cell.position();
+        let delta = area.wrap_delta(p1 - p0);
+        write_line(w, p0, p0 + delta)?;
+        // The edge crosses the border, so also draw it coming in from the other side.
+        if p0 + delta != p1 {
+            write_line(w, p1 - delta, p1)?;
+        }
+    }
+    writeln!(w, "</g>")?;
+
+    for nix in graph.node_indices() {
+        let p = graph[nix].cell.position();
+        writeln!(w,
+                 r#"<circle cx="{}" cy="{}" r="{}" fill="{}"/>"#,
+                 p.x,
+                 p.y,
+                 drawn_radius(&graph[nix].cell),
+                 color::hex_color(palette.cell_color(&graph[nix])))?;
+    }
+
+    writeln!(w, "</g>")?;
+    writeln!(w, "</svg>")
+}
+
+fn write_line<W: Write>(w: &mut W, p0: Vector2<f64>, p1: Vector2<f64>) -> io::Result<()> {
+    writeln!(w,
+             r#"<line x1="{}" y1="{}" x2="{}" y2="{}"/>"#,
+             p0.x,
+             p0.y,
+             p1.x,
+             p1.y)
+}